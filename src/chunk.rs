@@ -1,27 +1,110 @@
 use std::convert::TryFrom;
 
+use object::ObjAllocator;
 use value::Value;
 
-pub enum Op {
+pub enum InstructionFormat {
+    Simple,
+    Byte,
+    Bytes,
     Constant,
-    Nil,
-    True,
-    False,
-    Pop,
-    GetGlobal,
-    DefineGlobal,
-    SetGlobal,
-    Equal,
-    Greater,
-    Less,
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
-    Not,
-    Negate,
-    Print,
-    Return,
+    Invoke,
+    Jump,
+    Loop,
+}
+
+impl InstructionFormat {
+    pub fn length(&self) -> usize {
+        match self {
+            InstructionFormat::Simple => 1,
+            InstructionFormat::Byte | InstructionFormat::Constant => 2,
+            InstructionFormat::Bytes | InstructionFormat::Invoke | InstructionFormat::Jump |
+            InstructionFormat::Loop => 3,
+        }
+    }
+}
+
+// Every opcode is declared exactly once here; the enum, the byte conversions, and the
+// disassembler metadata are all generated from this table. The last column is the net number
+// of values the instruction pushes (negative when it pops more than it pushes).
+macro_rules! define_ops {
+    ($($op:ident => $name:literal, $format:ident, $effect:literal;)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum Op {
+            $($op,)*
+        }
+
+        impl Op {
+            const ALL: &'static [Op] = &[$(Op::$op,)*];
+
+            pub fn iter() -> std::slice::Iter<'static, Op> {
+                return Op::ALL.iter();
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Op::$op => $name,)*
+                }
+            }
+
+            pub fn format(&self) -> InstructionFormat {
+                match self {
+                    $(Op::$op => InstructionFormat::$format,)*
+                }
+            }
+
+            pub fn operand_bytes(&self) -> usize {
+                return self.format().length() - 1;
+            }
+
+            pub fn stack_effect(&self) -> isize {
+                match self {
+                    $(Op::$op => $effect,)*
+                }
+            }
+        }
+    };
+}
+
+define_ops! {
+    Constant => "OP_CONSTANT", Constant, 1;
+    Nil => "OP_NIL", Simple, 1;
+    True => "OP_TRUE", Simple, 1;
+    False => "OP_FALSE", Simple, 1;
+    Pop => "OP_POP", Simple, -1;
+    Swap => "OP_SWAP", Simple, 0;
+    GetLocal => "OP_GET_LOCAL", Byte, 1;
+    SetLocal => "OP_SET_LOCAL", Byte, 0;
+    GetGlobal => "OP_GET_GLOBAL", Constant, 1;
+    DefineGlobal => "OP_DEFINE_GLOBAL", Constant, -1;
+    SetGlobal => "OP_SET_GLOBAL", Constant, 0;
+    GetProperty => "OP_GET_PROPERTY", Constant, 0;
+    SetProperty => "OP_SET_PROPERTY", Constant, -1;
+    Equal => "OP_EQUAL", Simple, -1;
+    Greater => "OP_GREATER", Simple, -1;
+    Less => "OP_LESS", Simple, -1;
+    Add => "OP_ADD", Simple, -1;
+    AddLocals => "OP_ADD_LOCALS", Bytes, 1;
+    AddConstant => "OP_ADD_CONSTANT", Constant, 0;
+    Subtract => "OP_SUBTRACT", Simple, -1;
+    Multiply => "OP_MULTIPLY", Simple, -1;
+    Divide => "OP_DIVIDE", Simple, -1;
+    Range => "OP_RANGE", Simple, -1;
+    RangeInclusive => "OP_RANGE_INCLUSIVE", Simple, -1;
+    In => "OP_IN", Simple, -1;
+    Not => "OP_NOT", Simple, 0;
+    Negate => "OP_NEGATE", Simple, 0;
+    Concat => "OP_CONCAT", Byte, 1;
+    Print => "OP_PRINT", Simple, -1;
+    Echo => "OP_ECHO", Simple, -1;
+    Jump => "OP_JUMP", Jump, 0;
+    JumpIfFalse => "OP_JUMP_IF_FALSE", Jump, 0;
+    Loop => "OP_LOOP", Loop, 0;
+    IterNew => "OP_ITER_NEW", Simple, 1;
+    IterNext => "OP_ITER_NEXT", Jump, 1;
+    Call => "OP_CALL", Byte, 0;
+    Invoke => "OP_INVOKE", Invoke, 0;
+    Return => "OP_RETURN", Simple, 0;
 }
 
 impl Into<u8> for Op {
@@ -34,36 +117,48 @@ impl TryFrom<u8> for Op {
     type Error = ();
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        Ok(match value {
-            x if x == Op::Constant as u8 => Op::Constant,
-            x if x == Op::Nil as u8 => Op::Nil,
-            x if x == Op::True as u8 => Op::True,
-            x if x == Op::False as u8 => Op::False,
-            x if x == Op::Pop as u8 => Op::Pop,
-            x if x == Op::GetGlobal as u8 => Op::GetGlobal,
-            x if x == Op::DefineGlobal as u8 => Op::DefineGlobal,
-            x if x == Op::SetGlobal as u8 => Op::SetGlobal,
-            x if x == Op::Equal as u8 => Op::Equal,
-            x if x == Op::Greater as u8 => Op::Greater,
-            x if x == Op::Less as u8 => Op::Less,
-            x if x == Op::Add as u8 => Op::Add,
-            x if x == Op::Subtract as u8 => Op::Subtract,
-            x if x == Op::Multiply as u8 => Op::Multiply,
-            x if x == Op::Divide as u8 => Op::Divide,
-            x if x == Op::Not as u8 => Op::Not,
-            x if x == Op::Negate as u8 => Op::Negate,
-            x if x == Op::Print as u8 => Op::Print,
-            x if x == Op::Return as u8 => Op::Return,
-            _ => return Err(()),
-        })
+        return Op::ALL.get(value as usize).copied().ok_or(());
     }
 }
 
 
+#[derive(Debug, PartialEq)]
+pub enum AppendError {
+    Invalid(VerifyError),
+    TooManyConstants,
+}
+
+pub struct JumpHandle {
+    offset: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    InvalidOpcode { offset: usize, byte: u8 },
+    TruncatedInstruction { offset: usize },
+    ConstantOutOfRange { offset: usize, index: usize },
+    ExpectedStringConstant { offset: usize, index: usize },
+    JumpOutOfBounds { offset: usize },
+    JumpIntoInstruction { offset: usize, target: usize },
+    MissingReturn,
+}
+
+// How errors point at a line: "[line 3]", or "[helpers.vlx line 3]" when the code has a name.
+pub fn source_location(source_name: Option<&str>, line: usize) -> String {
+    match source_name {
+        Some(name) => return format!("[{name} line {line}]"),
+        None => return format!("[line {line}]"),
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Chunk {
     pub code: Vec<u8>,
     pub constants: Vec<Value>,
     pub lines: Vec<usize>,
+    pub max_stack: usize,
+    // Where the code came from: a file path or "<repl>". Errors include it when it is set.
+    pub source_name: Option<String>,
 }
 
 impl Chunk {
@@ -72,10 +167,16 @@ impl Chunk {
             code: Vec::new(),
             constants: Vec::new(),
             lines: Vec::new(),
+            max_stack: 0,
+            source_name: None,
         }
     }
 
     pub fn add_constant(&mut self, value: Value) -> usize {
+        if let Some(location) = self.constants.iter().position(|constant| constant.identical(&value)) {
+            return location;
+        }
+
         let location: usize = self.constants.len();
 
         self.constants.push(value);
@@ -83,8 +184,279 @@ impl Chunk {
         return location;
     }
 
+    // Links `other` onto the end of this chunk, so that it runs once this chunk's code is done.
+    // The trailing return is dropped, constants are moved into this chunk's pool, and jumps need
+    // no fixing up because their offsets are relative. On error the chunk is left unchanged.
+    pub fn append(
+        &mut self,
+        other: &Chunk,
+        allocator: &mut ObjAllocator,
+    ) -> Result<(), AppendError> {
+        other.verify().map_err(AppendError::Invalid)?;
+
+        let constant_count: usize = self.constants.len();
+        let mut relocated: Vec<u8> = Vec::with_capacity(other.constants.len());
+
+        for constant in other.constants.iter() {
+            let constant: Value = match constant {
+                Value::String(reference) => {
+                    let string: String = allocator.deref(*reference).clone();
+                    Value::String(allocator.intern(string))
+                },
+                _ => *constant,
+            };
+
+            let index: usize = self.add_constant(constant);
+            if index > u8::MAX as usize {
+                self.constants.truncate(constant_count);
+                return Err(AppendError::TooManyConstants);
+            }
+
+            relocated.push(index as u8);
+        }
+
+        if let Some(last) = self.last_instruction() {
+            if self.code[last] == Op::Return.into() {
+                self.truncate(last);
+            }
+        }
+
+        let mut offset: usize = 0;
+        while offset < other.code.len() {
+            let op: Op = Op::try_from(other.code[offset]).unwrap();
+            let length: usize = op.format().length();
+
+            let mut bytes: Vec<u8> = other.code[offset..offset + length].to_vec();
+            if let InstructionFormat::Constant | InstructionFormat::Invoke = op.format() {
+                bytes[1] = relocated[bytes[1] as usize];
+            }
+
+            for (index, byte) in bytes.into_iter().enumerate() {
+                self.write(byte, other.lines[offset + index]);
+            }
+
+            offset += length;
+        }
+
+        self.max_stack = self.compute_max_stack();
+
+        return Ok(());
+    }
+
+    // Follows every path through the code, jumps included, keeping the depth each instruction is
+    // first reached with. Compiled code reaches an instruction at the same depth on every path.
+    pub fn compute_max_stack(&self) -> usize {
+        let mut depths: Vec<Option<isize>> = vec![None; self.code.len()];
+        let mut pending: Vec<(usize, isize)> = vec![(0, 0)];
+        let mut max_depth: isize = 0;
+
+        while let Some((offset, depth)) = pending.pop() {
+            if offset >= self.code.len() || depths[offset].is_some() {
+                continue;
+            }
+            depths[offset] = Some(depth);
+
+            let op: Op = match Op::try_from(self.code[offset]) {
+                Ok(op) if offset + op.format().length() <= self.code.len() => op,
+                _ => continue,
+            };
+
+            let next: usize = offset + op.format().length();
+            let after: isize = depth + match op {
+                // The callee and its arguments are replaced by the single result.
+                Op::Call => -(self.code[offset + 1] as isize),
+                Op::Invoke => -(self.code[offset + 2] as isize),
+                Op::Concat => 1 - self.code[offset + 1] as isize,
+                _ => op.stack_effect(),
+            };
+            max_depth = max_depth.max(after);
+
+            let distance: usize = match op.format() {
+                InstructionFormat::Jump | InstructionFormat::Loop => {
+                    (self.code[offset + 1] as usize) << 8 | self.code[offset + 2] as usize
+                },
+                _ => 0,
+            };
+
+            // A jump is taken before the instruction's own effect; only falling through past
+            // OP_ITER_NEXT pushes the next value.
+            match op {
+                Op::Jump => pending.push((next + distance, depth)),
+                Op::JumpIfFalse | Op::IterNext => {
+                    pending.push((next + distance, depth));
+                    pending.push((next, after));
+                },
+                Op::Loop => {
+                    if let Some(target) = next.checked_sub(distance) {
+                        pending.push((target, depth));
+                    }
+                },
+                Op::Return => (),
+                _ => pending.push((next, after)),
+            }
+        }
+
+        return max_depth as usize;
+    }
+
+    pub fn emit(&mut self, op: Op, line: usize) -> () {
+        debug_assert!(op.format().length() == 1, "{} takes an operand", op.name());
+
+        self.write(op.into(), line);
+    }
+
+    pub fn emit_with_byte(&mut self, op: Op, operand: u8, line: usize) -> () {
+        debug_assert!(op.format().length() == 2, "{} does not take a byte operand", op.name());
+
+        self.write(op.into(), line);
+        self.write(operand, line);
+    }
+
+    pub fn emit_with_bytes(&mut self, op: Op, first: u8, second: u8, line: usize) -> () {
+        debug_assert!(op.format().length() == 3, "{} does not take two byte operands", op.name());
+
+        self.write(op.into(), line);
+        self.write(first, line);
+        self.write(second, line);
+    }
+
+    pub fn emit_with_short(&mut self, op: Op, operand: u16, line: usize) -> () {
+        debug_assert!(op.format().length() == 3, "{} does not take a short operand", op.name());
+
+        self.write(op.into(), line);
+        self.write((operand >> 8) as u8, line);
+        self.write(operand as u8, line);
+    }
+
+    pub fn emit_jump(&mut self, op: Op, line: usize) -> JumpHandle {
+        debug_assert!(matches!(op.format(), InstructionFormat::Jump), "{} is not a jump", op.name());
+
+        self.emit_with_short(op, u16::MAX, line);
+
+        return JumpHandle {
+            offset: self.code.len() - 2,
+        };
+    }
+
+    pub fn emit_loop(&mut self, start: usize, line: usize) -> Result<(), ()> {
+        let distance: usize = self.code.len() + 3 - start;
+
+        if distance > u16::MAX as usize {
+            return Err(());
+        }
+
+        self.emit_with_short(Op::Loop, distance as u16, line);
+
+        return Ok(());
+    }
+
+    pub fn patch_jump(&mut self, handle: JumpHandle) -> Result<(), ()> {
+        let distance: usize = self.code.len() - handle.offset - 2;
+
+        if distance > u16::MAX as usize {
+            return Err(());
+        }
+
+        self.code[handle.offset] = (distance >> 8) as u8;
+        self.code[handle.offset + 1] = distance as u8;
+
+        return Ok(());
+    }
+
+    // Checks everything the Runner takes on trust when it decodes operands without bounds or
+    // validity checks.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut boundaries: Vec<bool> = vec![false; self.code.len()];
+        let mut jumps: Vec<(usize, usize)> = Vec::new();
+        let mut last: Option<Op> = None;
+
+        let mut offset: usize = 0;
+        while offset < self.code.len() {
+            let op: Op = match Op::try_from(self.code[offset]) {
+                Ok(op) => op,
+                Err(_) => return Err(VerifyError::InvalidOpcode { offset, byte: self.code[offset] }),
+            };
+
+            let length: usize = op.format().length();
+            if offset + length > self.code.len() {
+                return Err(VerifyError::TruncatedInstruction { offset });
+            }
+
+            match op.format() {
+                InstructionFormat::Constant | InstructionFormat::Invoke => {
+                    let index: usize = self.code[offset + 1] as usize;
+
+                    match (op, self.constants.get(index)) {
+                        (_, None) => return Err(VerifyError::ConstantOutOfRange { offset, index }),
+                        (Op::Constant | Op::AddConstant, Some(_)) => (),
+                        (_, Some(Value::String(_))) => (),
+                        _ => return Err(VerifyError::ExpectedStringConstant { offset, index }),
+                    }
+                },
+                InstructionFormat::Jump | InstructionFormat::Loop => {
+                    let distance: usize =
+                        (self.code[offset + 1] as usize) << 8 | self.code[offset + 2] as usize;
+                    let target: Option<usize> = match op.format() {
+                        InstructionFormat::Jump => Some(offset + 3 + distance),
+                        _ => (offset + 3).checked_sub(distance),
+                    };
+
+                    match target {
+                        Some(target) if target < self.code.len() => jumps.push((offset, target)),
+                        _ => return Err(VerifyError::JumpOutOfBounds { offset }),
+                    }
+                },
+                InstructionFormat::Simple | InstructionFormat::Byte |
+                InstructionFormat::Bytes => (),
+            }
+
+            boundaries[offset] = true;
+            last = Some(op);
+            offset += length;
+        }
+
+        if let Some(&(offset, target)) = jumps.iter().find(|&&(_, target)| !boundaries[target]) {
+            return Err(VerifyError::JumpIntoInstruction { offset, target });
+        }
+
+        if last != Some(Op::Return) {
+            return Err(VerifyError::MissingReturn);
+        }
+
+        return Ok(());
+    }
+
+    // Drops the code from `length` on, which must be an instruction boundary.
+    pub fn truncate(&mut self, length: usize) -> () {
+        self.code.truncate(length);
+        self.lines.truncate(length);
+    }
+
     pub fn write(&mut self, code: u8, line: usize) -> () {
         self.code.push(code);
         self.lines.push(line);
     }
+
+    // The offset of the last whole instruction, found by walking from the start since operand
+    // bytes can look like opcodes.
+    fn last_instruction(&self) -> Option<usize> {
+        let mut last: Option<usize> = None;
+
+        let mut offset: usize = 0;
+        while offset < self.code.len() {
+            let op: Op = match Op::try_from(self.code[offset]) {
+                Ok(op) => op,
+                Err(_) => return None,
+            };
+
+            if offset + op.format().length() > self.code.len() {
+                return None;
+            }
+
+            last = Some(offset);
+            offset += op.format().length();
+        }
+
+        return last;
+    }
 }
@@ -1,22 +1,28 @@
+use std::collections::{HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::convert::TryInto;
 
-use chunk::{Chunk, Op};
-use debug::{DEBUG_PRINT_CODE};
+use chunk::{source_location, Chunk, JumpHandle, Op};
+use globals::Globals;
 use object::{ObjAllocator, ObjRef};
 use scanner::{Scanner, Token, TokenType};
 use value::Value;
 
 type ParseRuleFn = Option<fn(&mut Compiler, can_assign: bool) -> ()>;
 
+const NESTING_MAX: usize = 256;
+const LOCALS_MAX: usize = 256;
+
 #[derive(Clone, Copy)]
 enum Precedence {
     None,
+    Comma,
     Assignment,
     Or,
     And,
     Equality,
     Comparison,
+    Range,
     Term,
     Factor,
     Unary,
@@ -30,11 +36,13 @@ impl TryFrom<usize> for Precedence {
     fn try_from(value: usize) -> Result<Self, Self::Error> {
         Ok(match value {
             x if x == Precedence::None as usize => Precedence::None,
+            x if x == Precedence::Comma as usize => Precedence::Comma,
             x if x == Precedence::Assignment as usize => Precedence::Assignment,
             x if x == Precedence::Or as usize => Precedence::Or,
             x if x == Precedence::And as usize => Precedence::And,
             x if x == Precedence::Equality as usize => Precedence::Equality,
             x if x == Precedence::Comparison as usize => Precedence::Comparison,
+            x if x == Precedence::Range as usize => Precedence::Range,
             x if x == Precedence::Term as usize => Precedence::Term,
             x if x == Precedence::Factor as usize => Precedence::Factor,
             x if x == Precedence::Unary as usize => Precedence::Unary,
@@ -45,33 +53,144 @@ impl TryFrom<usize> for Precedence {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub location: String,
+    pub message: String,
+    pub source_name: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn render(&self) -> String {
+        let severity: &str = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+
+        return format!(
+            "{} {}{}: {}",
+            source_location(self.source_name.as_deref(), self.line),
+            severity,
+            self.location,
+            self.message,
+        );
+    }
+
+    pub fn to_json(&self) -> String {
+        let severity: &str = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        let source: String = match &self.source_name {
+            Some(name) => format!("\"source\":\"{}\",", escape_json(name)),
+            None => String::new(),
+        };
+
+        return format!(
+            "{{{}\"line\":{},\"column\":{},\"severity\":\"{}\",\"message\":\"{}\"}}",
+            source,
+            self.line,
+            self.column,
+            severity,
+            escape_json(&self.message),
+        );
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    let mut escaped: String = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32));
+            },
+            character => escaped.push(character),
+        }
+    }
+
+    return escaped;
+}
+
 pub struct Parser<'a> {
     pub scanner: Scanner<'a>,
     pub current: Token<'a>,
-    pub previous: Option<Token<'a>>,
+    pub previous: Token<'a>,
     pub had_error: bool,
+    pub had_warning: bool,
+    pub error_count: usize,
     pub panic_mode: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    pub source_name: Option<String>,
+    // Set by `abort`; nothing is reported after that.
+    aborted: bool,
+    // Tokens scanned ahead by `peek_next`, error tokens included, not yet reached by `advance`.
+    lookahead: VecDeque<Token<'a>>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(scanner: Scanner<'a>) -> Parser {
-        let mut scanner: Scanner = scanner;
-        let current: Token = scanner.scan_token();
+        // Until the first `advance`, both tokens are a placeholder on line 0 so that an error in
+        // the very first token goes through the usual reporting path.
+        let start: Token = Token {
+            token_type: TokenType::Error,
+            lexeme: "",
+            line: 0,
+            column: 0,
+        };
 
         Parser {
             scanner,
-            current,
-            previous: None,
+            current: start,
+            previous: start,
             had_error: false,
+            had_warning: false,
+            error_count: 0,
             panic_mode: false,
+            diagnostics: Vec::new(),
+            source_name: None,
+            aborted: false,
+            lookahead: VecDeque::new(),
+        }
+    }
+
+    // Reports `message` at the current token and gives up on the rest of the source, which is
+    // skipped so that the constructs left open around it unwind without errors of their own.
+    fn abort(&mut self, message: &str) -> () {
+        let token: Token<'a> = self.current;
+        self.report(&token, Severity::Error, message);
+
+        self.had_error = true;
+        self.aborted = true;
+
+        while self.current.token_type != TokenType::Eof {
+            self.advance();
         }
     }
 
-    fn advance(&mut self) -> () {
-        self.previous = Some(self.current);
+    pub fn advance(&mut self) -> () {
+        self.previous = self.current;
 
         loop {
-            self.current = self.scanner.scan_token();
+            self.current = match self.lookahead.pop_front() {
+                Some(token) => token,
+                None => self.scanner.scan_token(),
+            };
 
             if TokenType::Error != self.current.token_type {
                 break;
@@ -91,7 +210,8 @@ impl<'a> Parser<'a> {
     }
 
     fn error(&mut self, message: &str) -> () {
-        self.error_at(&self.previous.unwrap(), message);
+        let token = self.previous;
+        self.error_at(&token, message);
     }
 
     fn error_at(&mut self, token: &Token<'a>, message: &str) -> () {
@@ -101,23 +221,69 @@ impl<'a> Parser<'a> {
 
         self.panic_mode = true;
 
-        eprint!("[line {}] Error", token.line);
-
-        match token.token_type {
-            TokenType::Eof => eprint!(" at end"),
-            TokenType::Error => (),
-            _ => eprint!(" at '{}'", token.lexeme),
-        }
-
-        eprintln!(": {}", message);
+        self.report(token, Severity::Error, message);
 
         self.had_error = true;
+        self.error_count += 1;
     }
 
     fn error_at_current(&mut self, message: &str) -> () {
         let token = self.current;
         self.error_at(&token, message);
     }
+
+    // The token `advance` will move to after `current`. Error tokens are skipped as `advance`
+    // skips them, but are only reported once `advance` reaches them, so peeking never reports an
+    // error twice or out of order, and panic mode applies to them as usual.
+    pub fn peek_next(&mut self) -> Token<'a> {
+        let buffered: Option<&Token<'a>> = self.lookahead
+            .iter()
+            .find(|token| token.token_type != TokenType::Error);
+
+        if let Some(token) = buffered {
+            return *token;
+        }
+
+        loop {
+            let token: Token<'a> = self.scanner.scan_token();
+            self.lookahead.push_back(token);
+
+            if token.token_type != TokenType::Error {
+                return token;
+            }
+        }
+    }
+
+    fn report(&mut self, token: &Token<'a>, severity: Severity, message: &str) -> () {
+        if self.aborted {
+            return;
+        }
+
+        let location: String = match token.token_type {
+            TokenType::Eof => " at end".to_owned(),
+            TokenType::Error => String::new(),
+            _ => format!(" at '{}'", token.lexeme),
+        };
+
+        self.diagnostics.push(Diagnostic {
+            severity,
+            line: token.line,
+            column: token.column,
+            location,
+            message: message.to_owned(),
+            source_name: self.source_name.clone(),
+        });
+    }
+
+    fn warn_at(&mut self, token: &Token<'a>, message: &str) -> () {
+        if self.panic_mode {
+            return;
+        }
+
+        self.report(token, Severity::Warning, message);
+
+        self.had_warning = true;
+    }
 }
 
 pub struct ParseRule {
@@ -142,15 +308,29 @@ fn make_rules() -> Vec<ParseRule> {
             TokenType::LeftParen,
             ParseRule {
                 prefix: Some(|c, ca| c.grouping(ca)),
-                infix: None,
-                precedence: Precedence::None,
+                infix: Some(|c, ca| c.call(ca)),
+                precedence: Precedence::Call,
             }
         ),
         (TokenType::RightParen, ParseRule::default()),
         (TokenType::LeftBrace, ParseRule::default()),
         (TokenType::RightBrace, ParseRule::default()),
-        (TokenType::Dot, ParseRule::default()),
-        (TokenType::Comma, ParseRule::default()),
+        (
+            TokenType::Dot,
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, ca| c.dot(ca)),
+                precedence: Precedence::Call,
+            }
+        ),
+        (
+            TokenType::Comma,
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, ca| c.comma(ca)),
+                precedence: Precedence::Comma,
+            }
+        ),
         (TokenType::Semicolon, ParseRule::default()),
         (
             TokenType::Plus,
@@ -236,13 +416,35 @@ fn make_rules() -> Vec<ParseRule> {
                 precedence: Precedence::Comparison,
             }
         ),
+        (TokenType::DotDot,
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, ca| c.binary(ca)),
+                precedence: Precedence::Range,
+            }
+        ),
+        (TokenType::DotDotEqual,
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, ca| c.binary(ca)),
+                precedence: Precedence::Range,
+            }
+        ),
         (TokenType::Var, ParseRule::default()),
         (TokenType::Fun, ParseRule::default()),
         (TokenType::Class, ParseRule::default()),
         (TokenType::This, ParseRule::default()),
         (TokenType::Super, ParseRule::default()),
         (TokenType::If, ParseRule::default()),
+        (TokenType::In,
+            ParseRule {
+                prefix: None,
+                infix: Some(|c, ca| c.binary(ca)),
+                precedence: Precedence::Comparison,
+            }
+        ),
         (TokenType::Else, ParseRule::default()),
+        (TokenType::Do, ParseRule::default()),
         (TokenType::For, ParseRule::default()),
         (TokenType::While, ParseRule::default()),
         (TokenType::Return, ParseRule::default()),
@@ -306,24 +508,144 @@ fn make_rules() -> Vec<ParseRule> {
     return rules.into_iter().map(|(_, rule)| rule).collect::<Vec<ParseRule>>();
 }
 
+// Scans the expression as tokens, so that braces inside string literals, nested interpolations
+// included, don't count.
+fn find_closing_brace(value: &str) -> Option<usize> {
+    let mut scanner: Scanner = Scanner::new(value);
+    let mut depth: usize = 0;
+
+    loop {
+        let token: Token = scanner.scan_token();
+
+        match token.token_type {
+            TokenType::LeftBrace => depth += 1,
+            TokenType::RightBrace if depth == 0 => {
+                return Some(token.lexeme.as_ptr() as usize - value.as_ptr() as usize);
+            },
+            TokenType::RightBrace => depth -= 1,
+            TokenType::Eof => return None,
+            _ => (),
+        }
+    }
+}
+
+fn find_interpolation(value: &str) -> Option<usize> {
+    return value
+        .match_indices("${")
+        .map(|(index, _)| index)
+        .find(|&index| index == 0 || value.as_bytes()[index - 1] != b'\\');
+}
+
+// Whether the code only reads values and computes with them. Assignments, calls and property
+// access may have effects, so any of them makes the code impure.
+fn is_pure(code: &[u8]) -> bool {
+    let mut offset: usize = 0;
+
+    while offset < code.len() {
+        let op: Op = match Op::try_from(code[offset]) {
+            Ok(op) => op,
+            Err(_) => return false,
+        };
+
+        match op {
+            Op::Constant | Op::Nil | Op::True | Op::False | Op::Pop | Op::Swap | Op::GetLocal |
+            Op::GetGlobal | Op::Equal | Op::Greater | Op::Less | Op::Add | Op::AddLocals |
+            Op::AddConstant | Op::Subtract | Op::Multiply | Op::Divide | Op::Range |
+            Op::RangeInclusive | Op::In | Op::Not | Op::Negate | Op::Concat => (),
+            _ => return false,
+        }
+
+        offset += op.format().length();
+    }
+
+    return true;
+}
+
+// Backslashes that don't start a known escape are kept as they are.
+fn unescape(value: &str) -> Result<String, &'static str> {
+    let mut unescaped: String = String::with_capacity(value.len());
+    let mut rest: &str = value;
+
+    while let Some(index) = rest.find('\\') {
+        unescaped.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if rest.starts_with("\\${") {
+            unescaped.push_str("${");
+            rest = &rest[3..];
+        } else if rest.starts_with("\\x") {
+            let digits: &str = rest.get(2..4).unwrap_or("");
+
+            if digits.len() != 2 || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return Err("Expect two hex digits after '\\x'.");
+            }
+
+            let code: u8 = u8::from_str_radix(digits, 16).unwrap();
+            if code > 0x7f {
+                return Err("Hex escape must be at most '\\x7F'.");
+            }
+
+            unescaped.push(code as char);
+            rest = &rest[4..];
+        } else {
+            unescaped.push('\\');
+            rest = &rest[1..];
+        }
+    }
+
+    unescaped.push_str(rest);
+
+    return Ok(unescaped);
+}
+
+// Locals live in stack slots, in declaration order. Only `for` loops declare any so far: their
+// hidden iterator state is named "", which no identifier can match.
+struct Local<'a> {
+    name: &'a str,
+}
+
 pub struct Compiler<'a> {
     parser: Parser<'a>,
-    allocator: &'a ObjAllocator,
+    allocator: &'a mut ObjAllocator,
     current_chunk: &'a mut Chunk,
     rules: Vec<ParseRule>,
+    nesting_depth: usize,
+    echo_expressions: bool,
+    operand_start: usize,
+    defined_globals: HashSet<&'a str>,
+    globals: Option<&'a Globals>,
+    locals: Vec<Local<'a>>,
+    max_errors: Option<usize>,
+    optimize: bool,
 }
 
 impl<'a> Compiler<'a> {
-    pub fn new(source: &'a str, allocator: &'a mut ObjAllocator, chunk: &'a mut Chunk) -> Compiler<'a> {
+    pub fn new(
+        source: &'a str,
+        allocator: &'a mut ObjAllocator,
+        chunk: &'a mut Chunk,
+        echo_expressions: bool,
+    ) -> Compiler<'a> {
         return Compiler {
             parser: Parser::new(Scanner::new(source)),
             allocator,
             current_chunk: chunk,
             rules: make_rules(),
+            nesting_depth: 0,
+            echo_expressions,
+            operand_start: 0,
+            defined_globals: HashSet::new(),
+            globals: None,
+            // Slot zero belongs to the function being run; the name "" can't be referenced.
+            locals: vec![Local { name: "" }],
+            max_errors: None,
+            optimize: false,
         };
     }
 
     pub fn compile(&mut self) -> bool {
+        self.parser.advance();
+
         while !self.match_token(TokenType::Eof) {
             self.declaration();
         }
@@ -333,12 +655,101 @@ impl<'a> Compiler<'a> {
         return !self.parser.had_error;
     }
 
+    // With `print` scanned as a plain identifier, `print(...)` compiles to a call of whatever the
+    // global holds. Print tokens then never appear, so the rule table needs no change.
+    pub fn set_print_as_function(&mut self, enabled: bool) -> () {
+        self.parser.scanner.print_keyword = !enabled;
+    }
+
+    // Stops compiling once this many errors have been reported. Unlimited by default, and zero
+    // means unlimited too.
+    pub fn set_max_errors(&mut self, max_errors: usize) -> () {
+        self.max_errors = match max_errors {
+            0 => None,
+            _ => Some(max_errors),
+        };
+    }
+
+    // Fuses common instruction sequences into superinstructions as they are emitted.
+    pub fn set_optimize(&mut self, enabled: bool) -> () {
+        self.optimize = enabled;
+    }
+
+    // Names the source in diagnostics and in the chunk, for runtime errors. Unnamed by default.
+    pub fn set_source_name(&mut self, name: &str) -> () {
+        self.parser.source_name = Some(name.to_owned());
+        self.current_chunk.source_name = Some(name.to_owned());
+    }
+
+    // The globals the code will run against, so that redefining one defined by an earlier run,
+    // such as a previous REPL line, warns too. None by default.
+    pub fn set_globals(&mut self, globals: &'a Globals) -> () {
+        self.globals = Some(globals);
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        return &self.parser.diagnostics;
+    }
+
+    pub fn had_warning(&self) -> bool {
+        return self.parser.had_warning;
+    }
+
+    fn add_local(&mut self, name: &'a str) -> () {
+        if self.locals.len() == LOCALS_MAX {
+            self.parser.error("Too many local variables.");
+            return;
+        }
+
+        self.locals.push(Local { name });
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut count: usize = 0;
+
+        if !self.check_token(TokenType::RightParen) {
+            loop {
+                self.expression();
+
+                if count == u8::MAX as usize {
+                    self.parser.error("Can't have more than 255 arguments.");
+                }
+                count += 1;
+
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.parser.consume(TokenType::RightParen, "Expect ')' after arguments.");
+
+        return count.min(u8::MAX as usize) as u8;
+    }
+
     fn binary(&mut self, _can_assign: bool) -> () {
-        let operator_type: TokenType = self.parser.previous.unwrap().token_type;
+        let operator: Token = self.parser.previous;
+        let operator_type: TokenType = operator.token_type;
         let rule: &ParseRule = self.get_rule(operator_type);
 
+        let left_start: usize = self.operand_start;
+        let right_start: usize = self.current_chunk.code.len();
+
         self.parse_precedence((rule.precedence as usize + 1).try_into().unwrap());
 
+        let comparison: bool = match operator_type {
+            TokenType::BangEqual | TokenType::EqualEqual | TokenType::Greater |
+            TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => true,
+            _ => false,
+        };
+
+        let code: &[u8] = &self.current_chunk.code;
+        let variable: bool = right_start - left_start == 2 &&
+            (code[left_start] == Op::GetGlobal.into() || code[left_start] == Op::GetLocal.into());
+        if comparison && variable && code[left_start..right_start] == code[right_start..] {
+            self.parser.warn_at(&operator, "Comparison of a value with itself.");
+        }
+
         match operator_type {
             TokenType::BangEqual => {
                 self.emit_op(Op::Equal);
@@ -355,19 +766,65 @@ impl<'a> Compiler<'a> {
                 self.emit_op(Op::Greater);
                 self.emit_op(Op::Not);
             },
-            TokenType::Plus => self.emit_op(Op::Add),
+            TokenType::Plus => self.emit_add(left_start, right_start),
             TokenType::Minus => self.emit_op(Op::Subtract),
             TokenType::Star => self.emit_op(Op::Multiply),
             TokenType::Slash => self.emit_op(Op::Divide),
+            TokenType::DotDot => self.emit_op(Op::Range),
+            TokenType::DotDotEqual => self.emit_op(Op::RangeInclusive),
+            TokenType::In => self.emit_op(Op::In),
             _ => (),
         }
     }
 
+    // Only `for` loops declare locals, and they pop them again themselves; `var` always defines
+    // a global. So a block groups statements without opening a scope.
+    fn block(&mut self) -> () {
+        while !self.check_token(TokenType::RightBrace) && !self.check_token(TokenType::Eof) {
+            self.declaration();
+        }
+
+        self.parser.consume(TokenType::RightBrace, "Expect '}' after block.");
+    }
+
+    fn call(&mut self, _can_assign: bool) -> () {
+        let count: u8 = self.argument_list();
+        self.emit_with_byte(Op::Call, count);
+    }
+
     fn check_token(&self, token_type: TokenType) -> bool {
         return self.parser.current.token_type == token_type;
     }
 
+    fn comma(&mut self, _can_assign: bool) -> () {
+        self.emit_op(Op::Pop);
+
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    // Argument lists and variable initialisers use `expression`, where a comma ends the
+    // expression; only statements and parentheses accept the comma operator.
+    fn comma_expression(&mut self) -> () {
+        self.parse_precedence(Precedence::Comma);
+    }
+
+    // At the prompt the end of the line also ends a statement, so `print 1` needs no semicolon.
+    fn consume_semicolon(&mut self, message: &str) -> () {
+        if self.echo_expressions && self.check_token(TokenType::Eof) {
+            return;
+        }
+
+        self.parser.consume(TokenType::Semicolon, message);
+    }
+
+    // Errors are counted here rather than at the top level so that a block full of them stops
+    // just as soon.
     fn declaration(&mut self) -> () {
+        if self.max_errors.map_or(false, |max_errors| self.parser.error_count >= max_errors) {
+            self.parser.abort("Too many errors; aborting.");
+            return;
+        }
+
         if self.match_token(TokenType::Var) {
             self.var_declaration();
         } else {
@@ -379,39 +836,152 @@ impl<'a> Compiler<'a> {
         }
     }
 
-    fn define_variable(&mut self, global: u8) -> () {
-        self.emit_op(Op::DefineGlobal);
-        self.emit_byte(global);
+    fn define_variable(&mut self, global: Option<u8>) -> () {
+        self.emit_with_constant(Op::DefineGlobal, global);
     }
 
-    fn emit_byte(&mut self, byte: u8) -> () {
-        self.current_chunk.write(byte, self.parser.previous.as_ref().unwrap().line);
+    fn do_statement(&mut self) -> () {
+        let loop_start: usize = self.current_chunk.code.len();
+
+        self.statement();
+
+        self.parser.consume(TokenType::While, "Expect 'while' after 'do' body.");
+        self.parser.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.expression();
+        self.parser.consume(TokenType::RightParen, "Expect ')' after condition.");
+        self.consume_semicolon("Expect ';' after 'do' loop condition.");
+
+        let exit: JumpHandle = self.emit_jump(Op::JumpIfFalse);
+        self.emit_op(Op::Pop);
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit);
+        self.emit_op(Op::Pop);
+    }
+
+    fn dot(&mut self, can_assign: bool) -> () {
+        self.parser.consume(TokenType::Identifier, "Expect property name after '.'.");
+
+        let name: Token<'a> = self.parser.previous;
+        let name: Option<u8> = self.identifier_constant(&name);
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit_with_constant(Op::SetProperty, name);
+        } else if self.match_token(TokenType::LeftParen) {
+            let count: u8 = self.argument_list();
+
+            if let Some(name) = name {
+                self.emit_with_bytes(Op::Invoke, name, count);
+            }
+        } else {
+            self.emit_with_constant(Op::GetProperty, name);
+        }
     }
 
-    fn emit_bytes(&mut self, byte0: u8, byte1: u8) -> () {
-        self.emit_byte(byte0);
-        self.emit_byte(byte1);
+    // With optimization on, `local + local` becomes one OP_ADD_LOCALS and `... + constant` an
+    // OP_ADD_CONSTANT. Only whole operands are fused, and no jump can land inside an operand, so
+    // the rewritten code needs no fixing up.
+    fn emit_add(&mut self, left_start: usize, right_start: usize) -> () {
+        let code: &[u8] = &self.current_chunk.code;
+        let single = |start: usize, end: usize, op: Op| -> bool {
+            return end - start == 2 && code[start] == op.into();
+        };
+
+        if !self.optimize || self.parser.panic_mode {
+            self.emit_op(Op::Add);
+        } else if single(left_start, right_start, Op::GetLocal) &&
+            single(right_start, code.len(), Op::GetLocal) {
+            let (left, right) = (code[left_start + 1], code[right_start + 1]);
+
+            self.current_chunk.truncate(left_start);
+            self.emit_with_bytes(Op::AddLocals, left, right);
+        } else if single(right_start, code.len(), Op::Constant) {
+            let constant: u8 = code[right_start + 1];
+
+            self.current_chunk.truncate(right_start);
+            self.emit_with_byte(Op::AddConstant, constant);
+        } else {
+            self.emit_op(Op::Add);
+        }
     }
 
     fn emit_constant(&mut self, value: Value) -> () {
-        let constant: u8 = self.make_constant(value);
-        return self.emit_bytes(Op::Constant.into(), constant);
+        let constant: Option<u8> = self.make_constant(value);
+        self.emit_with_constant(Op::Constant, constant);
+    }
+
+    // Unlike other instructions, jumps and loops are emitted in panic mode too, since the handle
+    // must point at a real jump for `patch_jump`. The chunk never runs after an error anyway.
+    fn emit_jump(&mut self, op: Op) -> JumpHandle {
+        let line: usize = self.line();
+        return self.current_chunk.emit_jump(op, line);
     }
 
+    fn emit_literal(&mut self, value: &str) -> () {
+        match unescape(value) {
+            Ok(value) => self.emit_string(&value),
+            Err(message) => self.parser.error(message),
+        }
+    }
+
+    fn emit_loop(&mut self, start: usize) -> () {
+        let line: usize = self.line();
+
+        if self.current_chunk.emit_loop(start, line).is_err() {
+            self.parser.error("Loop body too large.");
+        }
+    }
+
+    // Nothing is emitted while the parser is in panic mode, so the rest of a statement that
+    // failed to compile never leaves half an instruction sequence in the chunk.
     fn emit_op(&mut self, op: Op) -> () {
-        self.emit_byte(op.into());
+        if self.parser.panic_mode {
+            return;
+        }
+
+        let line: usize = self.line();
+        self.current_chunk.emit(op, line);
     }
 
     fn emit_return(&mut self) -> () {
         self.emit_op(Op::Return);
     }
 
+    fn emit_string(&mut self, value: &str) -> () {
+        let reference: ObjRef<String> = self.allocator.intern(value.to_owned());
+
+        self.emit_constant(Value::String(reference));
+    }
+
+    fn emit_with_byte(&mut self, op: Op, operand: u8) -> () {
+        if self.parser.panic_mode {
+            return;
+        }
+
+        let line: usize = self.line();
+        self.current_chunk.emit_with_byte(op, operand, line);
+    }
+
+    fn emit_with_bytes(&mut self, op: Op, first: u8, second: u8) -> () {
+        if self.parser.panic_mode {
+            return;
+        }
+
+        let line: usize = self.line();
+        self.current_chunk.emit_with_bytes(op, first, second, line);
+    }
+
+    fn emit_with_constant(&mut self, op: Op, constant: Option<u8>) -> () {
+        if let Some(constant) = constant {
+            self.emit_with_byte(op, constant);
+        }
+    }
+
     fn end_compiler(&mut self) -> () {
         self.emit_return();
 
-        if DEBUG_PRINT_CODE && !self.parser.had_error {
-            self.current_chunk.dissassemble_chunk("code");
-        }
+        self.current_chunk.max_stack = self.current_chunk.compute_max_stack();
     }
 
     fn expression(&mut self) -> () {
@@ -419,10 +989,53 @@ impl<'a> Compiler<'a> {
     }
 
     fn expression_statement(&mut self) -> () {
+        let token: Token = self.parser.current;
+        let start: usize = self.current_chunk.code.len();
+
+        self.comma_expression();
+
+        self.consume_semicolon("Expect ';' after expression.");
+
+        let code: &[u8] = &self.current_chunk.code[start..];
+
+        if !self.echo_expressions && !self.parser.had_error && is_pure(code) {
+            self.parser.warn_at(&token, "Expression statement has no effect.");
+        }
+
+        if self.echo_expressions {
+            self.emit_op(Op::Echo);
+        } else {
+            self.emit_op(Op::Pop);
+        }
+    }
+
+    // The iterable and a cursor sit in two hidden locals below the loop variable, which gets a
+    // fresh slot on every iteration and is gone once the loop ends.
+    fn for_statement(&mut self) -> () {
+        self.parser.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        self.parser.consume(TokenType::Identifier, "Expect loop variable name.");
+        let name: Token<'a> = self.parser.previous;
+        self.parser.consume(TokenType::In, "Expect 'in' after loop variable.");
         self.expression();
+        self.parser.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+
+        self.emit_op(Op::IterNew);
+        self.add_local("");
+        self.add_local("");
+
+        let loop_start: usize = self.current_chunk.code.len();
+        let exit: JumpHandle = self.emit_jump(Op::IterNext);
+        self.add_local(name.lexeme);
 
-        self.parser.consume(TokenType::Semicolon, "Expect ';' after expression.");
+        self.statement();
 
+        self.locals.pop();
+        self.emit_op(Op::Pop);
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit);
+        self.locals.truncate(self.locals.len().saturating_sub(2));
+        self.emit_op(Op::Pop);
         self.emit_op(Op::Pop);
     }
 
@@ -431,19 +1044,93 @@ impl<'a> Compiler<'a> {
     }
 
     fn grouping(&mut self, _can_assign: bool) -> () {
-        self.expression();
+        self.comma_expression();
 
         self.parser.consume(TokenType::RightParen, "Expect ')' after expression.");
     }
 
-    fn identifier_constant(&mut self, name: &Token) -> u8 {
+    fn identifier_constant(&mut self, name: &Token) -> Option<u8> {
         let reference: ObjRef<String> = self.allocator.intern(name.lexeme.to_owned());
 
         return self.make_constant(Value::String(reference));
     }
 
+    fn interpolated_expression(&mut self, source: &'a str, line: usize) -> () {
+        let mut scanner: Scanner<'a> = Scanner::new(source);
+        scanner.line = line;
+        scanner.print_keyword = self.parser.scanner.print_keyword;
+
+        let scanner: Scanner<'a> = std::mem::replace(&mut self.parser.scanner, scanner);
+        let lookahead: VecDeque<Token<'a>> = std::mem::take(&mut self.parser.lookahead);
+        let current: Token<'a> = self.parser.current;
+        let previous: Token<'a> = self.parser.previous;
+
+        self.parser.advance();
+        self.expression();
+        self.parser.consume(TokenType::Eof, "Expect '}' after interpolated expression.");
+
+        self.parser.scanner = scanner;
+        self.parser.lookahead = lookahead;
+        self.parser.current = current;
+        self.parser.previous = previous;
+    }
+
+    fn interpolation(&mut self, value: &'a str) -> () {
+        let line: usize = self.parser.previous.line;
+        let mut parts: usize = 0;
+        let mut rest: &'a str = value;
+
+        while !rest.is_empty() {
+            let (literal, expression): (&'a str, Option<&'a str>) = match find_interpolation(rest) {
+                Some(start) => match find_closing_brace(&rest[(start + 2)..]) {
+                    Some(length) => {
+                        let literal: &'a str = &rest[..start];
+                        let expression: &'a str = &rest[(start + 2)..(start + 2 + length)];
+
+                        rest = &rest[(start + 2 + length + 1)..];
+
+                        (literal, Some(expression))
+                    },
+                    None => {
+                        self.parser.error("Unterminated interpolation.");
+                        return;
+                    },
+                },
+                None => {
+                    let literal: &'a str = rest;
+
+                    rest = "";
+
+                    (literal, None)
+                },
+            };
+
+            if !literal.is_empty() {
+                self.emit_literal(literal);
+                parts += 1;
+            }
+
+            if let Some(expression) = expression {
+                self.interpolated_expression(expression, line);
+                parts += 1;
+            }
+        }
+
+        // All the parts are joined by a single OP_CONCAT, so the result is only interned once.
+        if parts > u8::MAX as usize {
+            self.parser.error("Too many parts in an interpolated string.");
+            return;
+        }
+
+        self.emit_with_byte(Op::Concat, parts as u8);
+    }
+
+    fn line(&self) -> usize {
+        return self.parser.previous.line;
+    }
+
     fn literal(&mut self, _can_assign: bool) -> () {
-        match self.parser.previous.unwrap().token_type {
+        match self.parser.previous.token_type {
             TokenType::False => self.emit_op(Op::False),
             TokenType::Nil => self.emit_op(Op::Nil),
             TokenType::True => self.emit_op(Op::True),
@@ -451,15 +1138,15 @@ impl<'a> Compiler<'a> {
         }
     }
 
-    fn make_constant(&mut self, value: Value) -> u8 {
+    fn make_constant(&mut self, value: Value) -> Option<u8> {
         let constant = self.current_chunk.add_constant(value);
 
         if constant > std::u8::MAX as usize {
             self.parser.error("Too many constants in one chunk.");
-            return 0;
+            return None;
         }
 
-        return constant as u8;
+        return Some(constant as u8);
     }
 
     fn match_token(&mut self, token_type: TokenType) -> bool {
@@ -473,33 +1160,57 @@ impl<'a> Compiler<'a> {
     }
 
     fn named_variable(&mut self, name: &Token, can_assign: bool) -> () {
-        let arg: u8 = self.identifier_constant(&name);
+        let (get_op, set_op, arg): (Op, Op, Option<u8>) = match self.resolve_local(name) {
+            Some(slot) => (Op::GetLocal, Op::SetLocal, Some(slot)),
+            None => (Op::GetGlobal, Op::SetGlobal, self.identifier_constant(&name)),
+        };
 
-        if can_assign && self.match_token(TokenType::Equal) {
+        let op: Op = if can_assign && self.match_token(TokenType::Equal) {
             self.expression();
-            self.emit_op(Op::SetGlobal);
-            self.emit_byte(arg);
+            set_op
         } else {
-            self.emit_op(Op::GetGlobal);
-            self.emit_byte(arg);
+            get_op
+        };
+
+        // The operand is missing only when the constant table overflowed, which is reported.
+        if let Some(arg) = arg {
+            self.emit_with_byte(op, arg);
         }
     }
 
+    // Literals too large for a double are rejected rather than silently becoming infinity.
     fn number(&mut self, _can_assign: bool) -> () {
-        let value: f64 = self.parser.previous.unwrap().lexeme.parse().unwrap();
-        self.emit_constant(Value::Number(value));
+        let lexeme: &str = self.parser.previous.lexeme;
+
+        match lexeme.parse::<f64>() {
+            Ok(value) if value.is_infinite() => {
+                self.parser.error("Number literal is out of range.");
+            },
+            Ok(value) => self.emit_constant(Value::Number(value)),
+            Err(_) => self.parser.error(&format!("Invalid number literal '{lexeme}'.")),
+        }
     }
 
     fn parse_precedence(&mut self, precedence: Precedence) -> () {
+        if self.nesting_depth >= NESTING_MAX {
+            self.parser.error("Expression nesting too deep.");
+            return;
+        }
+
+        self.nesting_depth += 1;
+
         self.parser.advance();
 
-        let prefix_rule: ParseRuleFn = self.get_rule(self.parser.previous.unwrap().token_type).prefix;
+        let prefix_rule: ParseRuleFn = self.get_rule(self.parser.previous.token_type).prefix;
 
         let can_assign: bool = precedence as usize <= Precedence::Assignment as usize;
 
+        let start: usize = self.current_chunk.code.len();
+
         match prefix_rule {
             None => {
                 self.parser.error("Expect expression.");
+                self.nesting_depth -= 1;
                 return;
             }
             Some(prefix_rule) => prefix_rule(self, can_assign),
@@ -514,56 +1225,117 @@ impl<'a> Compiler<'a> {
 
             let infix_rule = rule.infix.unwrap();
             self.parser.advance();
+            self.operand_start = start;
             infix_rule(self, can_assign);
         }
 
         if can_assign && self.match_token(TokenType::Equal) {
             self.parser.error("Invalid assignment target.");
         }
+
+        self.nesting_depth -= 1;
     }
 
-    fn parse_variable(&mut self, error_message: &str) -> u8 {
+    fn parse_variable(&mut self, error_message: &str) -> Option<u8> {
         self.parser.consume(TokenType::Identifier, error_message);
 
-        return self.identifier_constant(&self.parser.previous.unwrap());
+        let name: Token<'a> = self.parser.previous;
+        return self.identifier_constant(&name);
+    }
+
+    fn patch_jump(&mut self, handle: JumpHandle) -> () {
+        if self.current_chunk.patch_jump(handle).is_err() {
+            self.parser.error("Too much code to jump over.");
+        }
     }
 
     fn print_statement(&mut self) ->() {
-        self.expression();
+        self.comma_expression();
 
-        self.parser.consume(TokenType::Semicolon, "Expect ';' after value.");
+        self.consume_semicolon("Expect ';' after value.");
 
         self.emit_op(Op::Print);
     }
 
+    // Records the global as defined, reporting whether this compile or an earlier run already
+    // defined it.
+    fn redefines_global(&mut self, name: &'a str) -> bool {
+        if !self.defined_globals.insert(name) {
+            return true;
+        }
+
+        return match self.globals {
+            Some(globals) => {
+                let reference: ObjRef<String> = self.allocator.intern(name.to_owned());
+
+                globals.contains_key(reference, self.allocator.hash(reference))
+            },
+            None => false,
+        };
+    }
+
+    fn resolve_local(&self, name: &Token) -> Option<u8> {
+        return self.locals
+            .iter()
+            .rposition(|local| local.name == name.lexeme)
+            .map(|slot| slot as u8);
+    }
+
+    // There are no functions yet, so every `return` is in top-level code.
+    fn return_statement(&mut self) -> () {
+        self.parser.error("Can't return from top-level code.");
+    }
+
+    // Blocks and loop bodies all come through here, so nesting them counts against the same
+    // limit as nested expressions.
     fn statement(&mut self) -> () {
+        if self.nesting_depth >= NESTING_MAX {
+            self.parser.abort("Statement nesting too deep.");
+            return;
+        }
+
+        self.nesting_depth += 1;
+
         if self.match_token(TokenType::Print) {
             self.print_statement();
+        } else if self.match_token(TokenType::Do) {
+            self.do_statement();
+        } else if self.match_token(TokenType::For) {
+            self.for_statement();
+        } else if self.match_token(TokenType::Return) {
+            self.return_statement();
+        } else if self.match_token(TokenType::LeftBrace) {
+            self.block();
         } else {
             self.expression_statement();
         }
+
+        self.nesting_depth -= 1;
     }
 
     fn string(&mut self, _can_assign: bool) -> () {
-        let lexeme: &str = self.parser.previous.unwrap().lexeme;
-        let value: &str = &lexeme[1..(lexeme.len() - 1)];
+        let lexeme: &str = self.parser.previous.lexeme;
+        let quotes: usize = if lexeme.starts_with("\"\"\"") { 3 } else { 1 };
+        let value: &str = &lexeme[quotes..(lexeme.len() - quotes)];
 
-        let reference: ObjRef<String> = self.allocator.intern(value.to_owned());
-
-        self.emit_constant(Value::String(reference));
+        if find_interpolation(value).is_some() {
+            self.interpolation(value);
+        } else {
+            self.emit_literal(value);
+        }
     }
 
     fn synchronize(&mut self) -> () {
         self.parser.panic_mode = false;
 
         while self.parser.current.token_type != TokenType::Eof {
-            if self.parser.previous.unwrap().token_type == TokenType::Semicolon {
+            if self.parser.previous.token_type == TokenType::Semicolon {
                 return;
             }
 
             match self.parser.current.token_type {
                 TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For| TokenType::If |
-                TokenType::While | TokenType::Print | TokenType::Return => return,
+                TokenType::While | TokenType::Do | TokenType::Print | TokenType::Return => return,
                 _ => (),
             }
 
@@ -572,7 +1344,7 @@ impl<'a> Compiler<'a> {
     }
 
     fn unary(&mut self, _can_assign: bool) -> () {
-        let operator_type: TokenType = self.parser.previous.unwrap().token_type;
+        let operator_type: TokenType = self.parser.previous.token_type;
 
         self.parse_precedence(Precedence::Unary);
 
@@ -584,7 +1356,12 @@ impl<'a> Compiler<'a> {
     }
 
     fn var_declaration(&mut self) -> () {
-        let global: u8 = self.parse_variable("Expect variable name.");
+        let global: Option<u8> = self.parse_variable("Expect variable name.");
+
+        let name: Token = self.parser.previous;
+        if name.token_type == TokenType::Identifier && self.redefines_global(name.lexeme) {
+            self.parser.warn_at(&name, "Redefinition of an existing global variable.");
+        }
 
         if self.match_token(TokenType::Equal) {
             self.expression();
@@ -592,12 +1369,13 @@ impl<'a> Compiler<'a> {
             self.emit_op(Op::Nil);
         }
 
-        self.parser.consume(TokenType::Semicolon, "Expect ';' after variable declaration.");
+        self.consume_semicolon("Expect ';' after variable declaration.");
 
         self.define_variable(global);
     }
 
     fn variable(&mut self, can_assign: bool) -> () {
-        self.named_variable(&self.parser.previous.unwrap(), can_assign);
+        let name: Token<'a> = self.parser.previous;
+        self.named_variable(&name, can_assign);
     }
 }
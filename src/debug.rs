@@ -1,73 +1,254 @@
-use std::convert::TryInto;
+use std::{
+    convert::TryInto,
+    fmt::{self, Write},
+};
 
-use chunk::{Chunk, Op};
-
-pub static DEBUG_TRACE_EXECUTION: bool = true;
-pub static DEBUG_PRINT_CODE: bool = true;
+use chunk::{Chunk, InstructionFormat, Op};
+use object::ObjAllocator;
+use value::Value;
 
 impl Chunk {
-    pub fn dissassemble_chunk(&self, name: &str) -> () {
-        println!("== {name} ==");
+    pub fn dissassemble_chunk(&self, name: &str, allocator: &ObjAllocator) -> () {
+        let mut output: String = String::new();
+        self.write_chunk(name, allocator, &mut output).unwrap();
+
+        eprint!("{output}");
+    }
+
+    pub fn dissassemble_instruction(&self, offset: usize, allocator: &ObjAllocator) -> usize {
+        let mut output: String = String::new();
+        let next: usize = self.write_instruction(offset, allocator, &mut output).unwrap();
+
+        eprint!("{output}");
+
+        return next;
+    }
+
+    pub fn first_offset_on_line(&self, line: usize) -> Option<usize> {
+        let mut offset: usize = 0;
+        while offset < self.code.len() {
+            if self.lines[offset] == line {
+                return Some(offset);
+            }
+
+            let op_code: Result<Op, ()> = self.code[offset].try_into();
+            offset += op_code.map_or(1, |op_code| op_code.format().length());
+        }
+
+        return None;
+    }
+
+    pub fn write_chunk(
+        &self,
+        name: &str,
+        allocator: &ObjAllocator,
+        out: &mut dyn Write,
+    ) -> fmt::Result {
+        writeln!(out, "== {name} ==")?;
 
         let mut offset: usize = 0;
         while offset < self.code.len() {
-            offset = self.dissassemble_instruction(offset);
+            offset = self.write_instruction(offset, allocator, out)?;
         }
+
+        writeln!(out, "== constants ==")?;
+
+        for (index, constant) in self.constants.iter().enumerate() {
+            let value: String = Chunk::constant_display(constant, allocator);
+
+            writeln!(out, "{index:04} {:<16} '{value}'", constant.type_name())?;
+        }
+
+        return Ok(());
     }
 
-    pub fn dissassemble_instruction(&self, offset: usize) -> usize {
-        print!("{offset:04} ");
+    pub fn write_instruction(
+        &self,
+        offset: usize,
+        allocator: &ObjAllocator,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
+        write!(out, "{offset:04} ")?;
 
         if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
-            print!("   | ");
+            write!(out, "   | ")?;
         } else {
-            print!("{:>4} ", self.lines[offset]);
+            write!(out, "{:>4} ", self.lines[offset])?;
         }
 
         let instruction: u8 = self.code[offset];
         let op_code: Result<Op, ()> = instruction.try_into();
         return match op_code {
-            Ok(op_code) => match op_code {
-                Op::Constant => self.constant_instruction("OP_CONSTANT", offset),
-                Op::Nil => self.simple_instruction("OP_NIL", offset),
-                Op::True => self.simple_instruction("OP_TRUE", offset),
-                Op::False => self.simple_instruction("OP_FALSE", offset),
-                Op::Pop => self.simple_instruction("OP_POP", offset),
-                Op::GetGlobal => self.constant_instruction("OP_GET_GLOBAL", offset),
-                Op::DefineGlobal => self.constant_instruction("OP_DEFINE_GLOBAL", offset),
-                Op::SetGlobal => self.constant_instruction("OP_SET_GLOBAL", offset),
-                Op::Equal => self.simple_instruction("OP_EQUAL", offset),
-                Op::Greater => self.simple_instruction("OP_GREATER", offset),
-                Op::Less => self.simple_instruction("OP_LESS", offset),
-                Op::Add => self.simple_instruction("OP_ADD", offset),
-                Op::Subtract => self.simple_instruction("OP_SUBTRACT", offset),
-                Op::Multiply => self.simple_instruction("OP_MULTIPLY", offset),
-                Op::Divide => self.simple_instruction("OP_DIVIDE", offset),
-                Op::Not => self.simple_instruction("OP_NOT", offset),
-                Op::Negate => self.simple_instruction("OP_NEGATE", offset),
-                Op::Print => self.simple_instruction("OP_PRINT", offset),
-                Op::Return => self.simple_instruction("OP_RETURN", offset),
+            Ok(op_code) => match op_code.format() {
+                InstructionFormat::Byte => self.byte_instruction(op_code.name(), offset, out),
+                InstructionFormat::Bytes => self.bytes_instruction(op_code.name(), offset, out),
+                InstructionFormat::Constant => {
+                    self.constant_instruction(op_code.name(), offset, allocator, out)
+                },
+                InstructionFormat::Simple => self.simple_instruction(op_code.name(), offset, out),
+                InstructionFormat::Invoke => {
+                    self.invoke_instruction(op_code.name(), offset, allocator, out)
+                },
+                InstructionFormat::Jump => self.jump_instruction(op_code.name(), true, offset, out),
+                InstructionFormat::Loop => self.jump_instruction(op_code.name(), false, offset, out),
             },
             _ => {
-                println!("Unknown opcode {}", instruction);
-                offset + 1
+                writeln!(out, "Unknown opcode {}", instruction)?;
+                Ok(offset + 1)
             }
         }
     }
 
-    fn constant_instruction(&self, name: &str, offset: usize) -> usize {
+    pub fn write_statistics(&self, out: &mut dyn Write) -> fmt::Result {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+
+        let mut offset: usize = 0;
+        while offset < self.code.len() {
+            let op_code: Result<Op, ()> = self.code[offset].try_into();
+            let (name, format) = match op_code {
+                Ok(op_code) => (op_code.name(), op_code.format()),
+                _ => ("UNKNOWN", InstructionFormat::Simple),
+            };
+
+            match counts.iter_mut().find(|(counted, _)| *counted == name) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((name, 1)),
+            }
+
+            offset += format.length();
+        }
+
+        writeln!(out, "== stats ==")?;
+        writeln!(out, "{:<16} {:>4}", "bytes", self.code.len())?;
+        writeln!(out, "{:<16} {:>4}", "constants", self.constants.len())?;
+        writeln!(out, "{:<16} {:>4}", "max stack", self.max_stack)?;
+
+        for (name, count) in counts {
+            writeln!(out, "{name:<16} {count:>4}")?;
+        }
+
+        return Ok(());
+    }
+
+    fn byte_instruction(
+        &self,
+        name: &str,
+        offset: usize,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
+        let operand: u8 = self.code[offset + 1];
+
+        writeln!(out, "{name:<16} {operand:>4}")?;
+
+        return Ok(offset + 2);
+    }
+
+    fn bytes_instruction(
+        &self,
+        name: &str,
+        offset: usize,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
+        let first: u8 = self.code[offset + 1];
+        let second: u8 = self.code[offset + 2];
+
+        writeln!(out, "{name:<16} {first:>4} {second:>4}")?;
+
+        return Ok(offset + 3);
+    }
+
+    // Numbers get thousands separators for readability; this only affects debug output.
+    fn constant_display(constant: &Value, allocator: &ObjAllocator) -> String {
+        let number: String = match constant {
+            Value::Number(number) => number.to_string(),
+            _ => return constant.stringify(allocator),
+        };
+
+        let (sign, unsigned) = match number.strip_prefix('-') {
+            Some(unsigned) => ("-", unsigned),
+            None => ("", number.as_str()),
+        };
+        let (integer, fraction) = match unsigned.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+
+        // inf and NaN have no digits to group.
+        if !integer.bytes().all(|byte| byte.is_ascii_digit()) {
+            return number;
+        }
+
+        let mut grouped: String = sign.to_owned();
+        for (index, digit) in integer.chars().enumerate() {
+            if index > 0 && (integer.len() - index) % 3 == 0 {
+                grouped.push(',');
+            }
+
+            grouped.push(digit);
+        }
+
+        if let Some(fraction) = fraction {
+            grouped.push('.');
+            grouped.push_str(fraction);
+        }
+
+        return grouped;
+    }
+
+    fn constant_instruction(
+        &self,
+        name: &str,
+        offset: usize,
+        allocator: &ObjAllocator,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
         let constant: u8 = self.code[offset + 1];
+        let value: String = Chunk::constant_display(&self.constants[constant as usize], allocator);
+
+        writeln!(out, "{name:<16} {constant:>4} '{value}'")?;
+
+        return Ok(offset + 2);
+    }
+
+    fn invoke_instruction(
+        &self,
+        name: &str,
+        offset: usize,
+        allocator: &ObjAllocator,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
+        let constant: u8 = self.code[offset + 1];
+        let count: u8 = self.code[offset + 2];
+        let value: String = Chunk::constant_display(&self.constants[constant as usize], allocator);
+
+        writeln!(out, "{name:<16} ({count} args) {constant:>4} '{value}'")?;
+
+        return Ok(offset + 3);
+    }
+
+    fn jump_instruction(
+        &self,
+        name: &str,
+        forward: bool,
+        offset: usize,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
+        let distance: usize = (self.code[offset + 1] as usize) << 8 | self.code[offset + 2] as usize;
+        let target: usize = if forward { offset + 3 + distance } else { offset + 3 - distance };
 
-        print!("{name:<16} {constant:>4} '");
-        self.constants[constant as usize].print();
-        println!("'");
+        writeln!(out, "{name:<16} {offset:>4} -> {target}")?;
 
-        return offset + 2;
+        return Ok(offset + 3);
     }
 
-    fn simple_instruction(&self, name: &str, offset: usize) -> usize {
-        println!("{name}");
+    fn simple_instruction(
+        &self,
+        name: &str,
+        offset: usize,
+        out: &mut dyn Write,
+    ) -> Result<usize, fmt::Error> {
+        writeln!(out, "{name}")?;
 
-        return offset + 1;
+        return Ok(offset + 1);
     }
 }
@@ -0,0 +1,124 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use chunk::Chunk;
+use globals::Globals;
+use object::ObjAllocator;
+use value::Value;
+
+pub struct DebugContext<'a> {
+    pub chunk: &'a Chunk,
+    pub offset: usize,
+    pub stack: &'a [Value],
+    pub globals: &'a Globals,
+    pub allocator: &'a ObjAllocator,
+}
+
+pub trait Debugger: Send {
+    fn before_instruction(&mut self, context: &DebugContext) -> ();
+}
+
+// Reads commands from stdin and writes everything it has to say to stderr, so the program's own
+// output on stdout is left untouched.
+pub struct StdinDebugger {
+    stepping: bool,
+    breakpoints: HashSet<usize>,
+}
+
+impl StdinDebugger {
+    pub fn new() -> StdinDebugger {
+        return StdinDebugger {
+            stepping: true,
+            breakpoints: HashSet::new(),
+        };
+    }
+
+    fn print_global(&self, context: &DebugContext, name: &str) -> () {
+        let value: Option<&Value> = context.allocator
+            .find_interned(name)
+            .and_then(|reference| {
+                context.globals.get(reference, context.allocator.hash(reference))
+            });
+
+        match value {
+            Some(value) => eprintln!("{name} = {}", value.stringify(context.allocator)),
+            None => eprintln!("Undefined variable '{name}'."),
+        }
+    }
+
+    fn print_stack(&self, context: &DebugContext) -> () {
+        for value in context.stack.iter() {
+            eprint!("[ {} ]", value.stringify(context.allocator));
+        }
+
+        eprintln!("");
+    }
+
+    fn read_command(&self) -> Option<String> {
+        eprint!("(debug) ");
+        io::stderr().flush().unwrap();
+
+        let mut line: String = String::new();
+
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => return Some(line.trim().to_owned()),
+        }
+    }
+
+    fn set_breakpoint(&mut self, context: &DebugContext, line: &str) -> () {
+        let offset: Option<usize> = line
+            .parse()
+            .ok()
+            .and_then(|line| context.chunk.first_offset_on_line(line));
+
+        match offset {
+            Some(offset) => {
+                self.breakpoints.insert(offset);
+                eprintln!("Breakpoint set at line {line} (offset {offset:04}).");
+            },
+            None => eprintln!("No code on line {line}."),
+        }
+    }
+}
+
+impl Debugger for StdinDebugger {
+    fn before_instruction(&mut self, context: &DebugContext) -> () {
+        if !self.stepping && !self.breakpoints.contains(&context.offset) {
+            return;
+        }
+
+        context.chunk.dissassemble_instruction(context.offset, context.allocator);
+
+        loop {
+            // Running out of commands lets the program finish instead of hanging on stdin.
+            let command: String = match self.read_command() {
+                Some(command) => command,
+                None => {
+                    self.stepping = false;
+                    self.breakpoints.clear();
+                    return;
+                },
+            };
+
+            let words: Vec<&str> = command.split_whitespace().collect();
+
+            match words.as_slice() {
+                ["s"] => {
+                    self.stepping = true;
+                    return;
+                },
+                ["c"] => {
+                    self.stepping = false;
+                    return;
+                },
+                ["b", line] => self.set_breakpoint(context, line),
+                ["p", name] => self.print_global(context, name),
+                ["stack"] => self.print_stack(context),
+                _ => eprintln!("Unknown command '{command}'."),
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::slice;
+
+use object::ObjRef;
+use table::Table;
+use value::Value;
+
+// Globals are kept in definition order so that anything listing them (the REPL, the debugger,
+// tests) sees the same order on every run. The table only points into the entry list. Names are
+// looked up with their hash from `ObjAllocator::hash`.
+pub struct Globals {
+    entries: Vec<(ObjRef<String>, Value)>,
+    frozen: Vec<bool>,
+    indices: Table<usize>,
+}
+
+impl Globals {
+    pub fn new() -> Globals {
+        return Globals {
+            entries: Vec::new(),
+            frozen: Vec::new(),
+            indices: Table::new(),
+        };
+    }
+
+    pub fn with_capacity(capacity: usize) -> Globals {
+        return Globals {
+            entries: Vec::with_capacity(capacity),
+            frozen: Vec::with_capacity(capacity),
+            indices: Table::with_capacity(capacity),
+        };
+    }
+
+    pub fn clear(&mut self) -> () {
+        self.entries.clear();
+        self.frozen.clear();
+        self.indices.clear();
+    }
+
+    pub fn contains_key(&self, name: ObjRef<String>, hash: u32) -> bool {
+        return self.indices.contains_key(name, hash);
+    }
+
+    // Only a defined global can be frozen.
+    pub fn freeze(&mut self, name: ObjRef<String>, hash: u32) -> () {
+        if let Some(&index) = self.indices.get(name, hash) {
+            self.frozen[index] = true;
+        }
+    }
+
+    pub fn get(&self, name: ObjRef<String>, hash: u32) -> Option<&Value> {
+        return self.indices.get(name, hash).map(|&index| &self.entries[index].1);
+    }
+
+    pub fn insert(&mut self, name: ObjRef<String>, hash: u32, value: Value) -> () {
+        match self.indices.get(name, hash) {
+            Some(&index) => self.entries[index].1 = value,
+            None => {
+                self.indices.set(name, hash, self.entries.len());
+                self.entries.push((name, value));
+                self.frozen.push(false);
+            },
+        }
+    }
+
+    pub fn is_frozen(&self, name: ObjRef<String>, hash: u32) -> bool {
+        return self.indices.get(name, hash).map_or(false, |&index| self.frozen[index]);
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (ObjRef<String>, Value)> {
+        return self.entries.iter();
+    }
+}
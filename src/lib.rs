@@ -0,0 +1,55 @@
+pub mod chunk;
+pub mod compiler;
+pub mod debug;
+pub mod debugger;
+pub mod globals;
+pub mod methods;
+pub mod natives;
+pub mod object;
+pub mod scanner;
+pub mod table;
+pub mod value;
+pub mod value_key;
+pub mod velox;
+pub mod vm;
+
+use chunk::Chunk;
+use compiler::{Compiler, Diagnostic};
+use object::ObjAllocator;
+
+/// Compiles `source` without running it and returns the disassembled chunk, followed by its
+/// constants table. Strings are interned in a throwaway allocator, so string constants are
+/// rendered by content.
+///
+/// On failure, returns every diagnostic the compiler reported, warnings included.
+///
+/// ```
+/// let assembly: String = rvelox::compile_to_assembly("print 1+2;").unwrap();
+///
+/// assert_eq!(
+///     assembly,
+///     "== code ==\n\
+///      0000    1 OP_CONSTANT         0 '1'\n\
+///      0002    | OP_CONSTANT         1 '2'\n\
+///      0004    | OP_ADD\n\
+///      0005    | OP_PRINT\n\
+///      0006    | OP_RETURN\n\
+///      == constants ==\n\
+///      0000 number           '1'\n\
+///      0001 number           '2'\n"
+/// );
+/// ```
+pub fn compile_to_assembly(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let mut chunk: Chunk = Chunk::new();
+    let mut compiler: Compiler = Compiler::new(source, &mut allocator, &mut chunk, false);
+
+    if !compiler.compile() {
+        return Err(compiler.diagnostics().to_vec());
+    }
+
+    let mut assembly: String = String::new();
+    chunk.write_chunk("code", &allocator, &mut assembly).unwrap();
+
+    return Ok(assembly);
+}
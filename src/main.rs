@@ -1,12 +1,5 @@
-mod chunk;
-mod compiler;
-mod debug;
-mod object;
-mod scanner;
-mod value;
-mod velox;
-mod vm;
+extern crate rvelox;
 
 fn main() {
-    velox::Velox::new().main();
+    rvelox::velox::Velox::new().main();
 }
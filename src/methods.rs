@@ -0,0 +1,142 @@
+use object::{ObjAllocator, ObjRef, Range};
+use value::Value;
+
+type MethodFn = fn(&mut ObjAllocator, Value, &[Value]) -> Result<Value, String>;
+
+pub struct Method {
+    pub type_name: &'static str,
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: MethodFn,
+}
+
+const fn method(
+    type_name: &'static str,
+    name: &'static str,
+    arity: usize,
+    function: MethodFn,
+) -> Method {
+    return Method { type_name, name, arity, function };
+}
+
+// Built-in methods on primitive receivers. Adding one is a single entry here.
+pub static METHODS: &[Method] = &[
+    method("buffer", "append", 1, |allocator, receiver, args| {
+        buffer_append(allocator, receiver, args[0])
+    }),
+    method("buffer", "len", 0, |allocator, receiver, _| buffer_len(allocator, receiver)),
+    method("buffer", "to_string", 0, |allocator, receiver, _| {
+        buffer_to_string(allocator, receiver)
+    }),
+    method("number", "abs", 0, |_, receiver, _| number(receiver, f64::abs)),
+    method("number", "ceil", 0, |_, receiver, _| number(receiver, f64::ceil)),
+    method("number", "floor", 0, |_, receiver, _| number(receiver, f64::floor)),
+    method("number", "round", 0, |_, receiver, _| number(receiver, f64::round)),
+    method("number", "max", 1, |_, receiver, args| numbers(receiver, args[0], f64::max)),
+    method("number", "min", 1, |_, receiver, args| numbers(receiver, args[0], f64::min)),
+    method("range", "len", 0, |allocator, receiver, _| range_len(allocator, receiver)),
+    method("string", "len", 0, |allocator, receiver, _| string_len(allocator, receiver)),
+    method("string", "lower", 0, |allocator, receiver, _| {
+        string(allocator, receiver, str::to_lowercase)
+    }),
+    method("string", "upper", 0, |allocator, receiver, _| {
+        string(allocator, receiver, str::to_uppercase)
+    }),
+];
+
+pub fn find_method(receiver: &Value, name: &str) -> Option<&'static Method> {
+    return METHODS
+        .iter()
+        .find(|method| method.type_name == receiver.type_name() && method.name == name);
+}
+
+// Returns the buffer itself so that appends can be chained.
+fn buffer_append(
+    allocator: &mut ObjAllocator,
+    receiver: Value,
+    argument: Value,
+) -> Result<Value, String> {
+    match receiver {
+        Value::Buffer(reference) => {
+            let text: String = argument.stringify(allocator);
+            allocator.deref_mut(reference).contents.push_str(&text);
+
+            return Ok(receiver);
+        },
+        _ => unreachable!("buffer method called on {}", receiver.type_name()),
+    }
+}
+
+fn buffer_len(allocator: &mut ObjAllocator, receiver: Value) -> Result<Value, String> {
+    match receiver {
+        Value::Buffer(reference) => {
+            return Ok(Value::Number(allocator.deref(reference).contents.chars().count() as f64));
+        },
+        _ => unreachable!("buffer method called on {}", receiver.type_name()),
+    }
+}
+
+fn buffer_to_string(allocator: &mut ObjAllocator, receiver: Value) -> Result<Value, String> {
+    match receiver {
+        Value::Buffer(reference) => {
+            let contents: String = allocator.deref(reference).contents.clone();
+
+            return Ok(Value::String(allocator.intern(contents)));
+        },
+        _ => unreachable!("buffer method called on {}", receiver.type_name()),
+    }
+}
+
+fn number(receiver: Value, function: fn(f64) -> f64) -> Result<Value, String> {
+    match receiver {
+        Value::Number(value) => return Ok(Value::Number(function(value))),
+        _ => unreachable!("number method called on {}", receiver.type_name()),
+    }
+}
+
+fn numbers(
+    receiver: Value,
+    argument: Value,
+    function: fn(f64, f64) -> f64,
+) -> Result<Value, String> {
+    match (receiver, argument) {
+        (Value::Number(a), Value::Number(b)) => return Ok(Value::Number(function(a, b))),
+        _ => return Err(format!("Argument must be a number, got {}.", argument.type_name())),
+    }
+}
+
+fn range_len(allocator: &mut ObjAllocator, receiver: Value) -> Result<Value, String> {
+    match receiver {
+        Value::Range(reference) => {
+            let range: &Range = allocator.deref(reference);
+
+            return Ok(Value::Number(range.len() as f64));
+        },
+        _ => unreachable!("range method called on {}", receiver.type_name()),
+    }
+}
+
+fn string(
+    allocator: &mut ObjAllocator,
+    receiver: Value,
+    function: fn(&str) -> String,
+) -> Result<Value, String> {
+    match receiver {
+        Value::String(reference) => {
+            let value: String = function(allocator.deref(reference).as_str());
+            let reference: ObjRef<String> = allocator.intern(value);
+
+            return Ok(Value::String(reference));
+        },
+        _ => unreachable!("string method called on {}", receiver.type_name()),
+    }
+}
+
+fn string_len(allocator: &mut ObjAllocator, receiver: Value) -> Result<Value, String> {
+    match receiver {
+        Value::String(reference) => {
+            return Ok(Value::Number(allocator.deref(reference).chars().count() as f64));
+        },
+        _ => unreachable!("string method called on {}", receiver.type_name()),
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    fmt,
+    io::Write,
+    sync::OnceLock,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use object::{Buffer, ObjAllocator};
+use value::Value;
+use velox::VeloxOptions;
+
+// Natives that print write to the VM's output.
+type NativeFn =
+    fn(&mut ObjAllocator, &VeloxOptions, &mut dyn Write, &[Value]) -> Result<Value, String>;
+
+pub struct Native {
+    pub name: &'static str,
+    // None takes any number of arguments.
+    pub arity: Option<usize>,
+    pub function: NativeFn,
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return write!(formatter, "<native fn {}>", self.name);
+    }
+}
+
+const fn native(name: &'static str, arity: Option<usize>, function: NativeFn) -> Native {
+    return Native { name, arity, function };
+}
+
+// Built-in functions, found when a global of the same name is not defined. `print` can only be
+// named under `--print-as-function`; otherwise it is a keyword.
+pub static NATIVES: &[Native] = &[
+    native("buffer", Some(0), |allocator, _, _, _| {
+        Ok(Value::Buffer(allocator.alloc(Buffer { contents: String::new() })))
+    }),
+    native("clock", Some(0), clock),
+    native("clone", Some(1), clone),
+    native("print", None, print),
+    native("time", Some(0), time),
+];
+
+// The instant `clock` measures from, fixed by its first call.
+static CLOCK_START: OnceLock<Instant> = OnceLock::new();
+
+pub fn find_native(name: &str) -> Option<&'static Native> {
+    return NATIVES.iter().find(|native| native.name == name);
+}
+
+// Monotonic seconds, for measuring elapsed time; only differences between calls are meaningful.
+fn clock(
+    _: &mut ObjAllocator,
+    _: &VeloxOptions,
+    _: &mut dyn Write,
+    _: &[Value],
+) -> Result<Value, String> {
+    let start: &Instant = CLOCK_START.get_or_init(Instant::now);
+
+    return Ok(Value::Number(start.elapsed().as_secs_f64()));
+}
+
+// Buffers are the only mutable values, so they are the only ones copied; everything else is
+// immutable and sharing it is indistinguishable from copying it.
+fn clone(
+    allocator: &mut ObjAllocator,
+    _: &VeloxOptions,
+    _: &mut dyn Write,
+    args: &[Value],
+) -> Result<Value, String> {
+    match args[0] {
+        Value::Buffer(reference) => {
+            let contents: String = allocator.deref(reference).contents.clone();
+
+            return Ok(Value::Buffer(allocator.alloc(Buffer { contents })));
+        },
+        value => return Ok(value),
+    }
+}
+
+fn print(
+    allocator: &mut ObjAllocator,
+    options: &VeloxOptions,
+    output: &mut dyn Write,
+    args: &[Value],
+) -> Result<Value, String> {
+    let parts: Vec<String> = args
+        .iter()
+        .map(|value| value.display(allocator, options))
+        .collect();
+
+    writeln!(output, "{}", parts.join(" ")).unwrap();
+
+    return Ok(Value::Nil);
+}
+
+// Seconds since the Unix epoch, from the system clock, which can jump.
+fn time(
+    _: &mut ObjAllocator,
+    _: &VeloxOptions,
+    _: &mut dyn Write,
+    _: &[Value],
+) -> Result<Value, String> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => return Ok(Value::Number(duration.as_secs_f64())),
+        Err(_) => return Err("System clock is set before the Unix epoch.".to_owned()),
+    }
+}
@@ -1,9 +1,16 @@
-pub trait ObjTrait {
+use std::any::Any;
+
+use table::{self, Table};
+
+// Objects must be Send so that a VM, allocator included, can move between threads.
+pub trait ObjTrait: Send {
     fn size(&self) -> usize;
 
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-#[derive(Hash)]
 pub struct ObjRef<T: ObjTrait> {
     index: usize,
     _marker: std::marker::PhantomData<T>,
@@ -19,16 +26,86 @@ impl<T: ObjTrait> Copy for ObjRef<T> {}
 
 impl<T: ObjTrait> Eq for ObjRef<T> {}
 
-// impl hash::Hash for ObjRef<String> {
-//     fn hash<H: hash::Hasher>(&self, state: &mut H) {
-//         return self.index.hash(state);
-//     }
-// }
+// Only the index: the contents need the allocator, which `Value::formatter` provides.
+impl<T: ObjTrait> std::fmt::Debug for ObjRef<T> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(formatter, "ObjRef({})", self.index);
+    }
+}
+
+// Hashed by index alone, so that any object type can be hashed by identity.
+impl<T: ObjTrait> std::hash::Hash for ObjRef<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        return self.index.hash(state);
+    }
+}
 
 impl ObjTrait for String {
     fn size(&self) -> usize {
         return std::mem::size_of::<String>() + self.as_bytes().len();
     }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
+}
+
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn contains(&self, value: f64) -> bool {
+        return self.start <= value && (value < self.end || self.inclusive && value == self.end);
+    }
+
+    // The number of values `start`, `start + 1`, ... that fall in the range.
+    pub fn len(&self) -> usize {
+        let span: f64 = self.end - self.start;
+        let len: f64 = if self.inclusive { span.floor() + 1.0 } else { span.ceil() };
+
+        return len.max(0.0) as usize;
+    }
+}
+
+impl ObjTrait for Range {
+    fn size(&self) -> usize {
+        return std::mem::size_of::<Range>();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
+}
+
+// A growable string for building text piece by piece. Unlike strings it is mutable and never
+// interned, so appending to it creates no intermediate objects.
+pub struct Buffer {
+    pub contents: String,
+}
+
+impl ObjTrait for Buffer {
+    fn size(&self) -> usize {
+        return std::mem::size_of::<Buffer>() + self.contents.capacity();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
 }
 
 impl<T: ObjTrait> PartialEq for ObjRef<T> {
@@ -37,29 +114,55 @@ impl<T: ObjTrait> PartialEq for ObjRef<T> {
     }
 }
 
+#[derive(Debug)]
+pub enum DerefError {
+    OutOfBounds,
+    TypeMismatch,
+}
+
 pub struct ObjAllocator {
     objects: Vec<ObjHeader>, // TODO: Make optional with GC
-    strings: std::collections::HashMap<String, ObjRef<String>>,
+    strings: Table<()>,
 }
 
 impl ObjAllocator {
     pub fn new() -> ObjAllocator {
         ObjAllocator {
             objects: Vec::new(),
-            strings: std::collections::HashMap::new(),
+            strings: Table::new(),
         }
     }
 
+    pub fn with_capacity(strings: usize) -> ObjAllocator {
+        ObjAllocator {
+            objects: Vec::new(),
+            strings: Table::with_capacity(strings),
+        }
+    }
+
+    // Computed from the objects as they are now, since a buffer grows after it is allocated.
+    pub fn allocated_bytes(&self) -> usize {
+        return self.objects
+            .iter()
+            .map(|header| std::mem::size_of::<ObjHeader>() + header.obj.size())
+            .sum();
+    }
+
+    pub fn find_interned(&self, value: &str) -> Option<ObjRef<String>> {
+        return self.find_string(value, table::hash_string(value));
+    }
+
     pub fn alloc<T: ObjTrait + 'static>(&mut self, obj: T) -> ObjRef<T> {
         let size: usize = obj.size() + std::mem::size_of::<ObjHeader>();
 
         let entry: ObjHeader = ObjHeader {
             size,
+            hash: 0,
             obj: Box::new(obj),
         };
 
-        self.objects.push(entry);
         let index: usize = self.objects.len();
+        self.objects.push(entry);
 
         return ObjRef {
             index,
@@ -67,30 +170,70 @@ impl ObjAllocator {
         }
     }
 
+    // Computed once, when the string is interned. Other strings are never table keys.
+    pub fn hash(&self, reference: ObjRef<String>) -> u32 {
+        return self.objects[reference.index].hash;
+    }
+
+    pub fn is_interned(&self, reference: ObjRef<String>) -> bool {
+        return self.strings.contains_key(reference, self.hash(reference));
+    }
+
     pub fn intern(&mut self, name: String) -> ObjRef<String> {
-        match self.strings.get(&name) {
-            Some(&value) => value,
+        let hash: u32 = table::hash_string(&name);
+
+        match self.find_string(&name, hash) {
+            Some(value) => value,
             None => {
                 let reference: ObjRef<String> = self.alloc(name);
-                self.strings.insert(name, reference);
+                self.objects[reference.index].hash = hash;
+                self.strings.set(reference, hash, ());
 
                 reference
             }
         }
     }
 
-    pub fn deref<T: ObjTrait>(&self, reference: ObjRef<T>) -> &T {
-        self.objects[reference.index]
-            .as_ref()
-            .unwrap()
-            .obj
-            .as_any()
-            .downcast_ref()
-            .unwrap_or_else(|| None /* TODO: Panic */);
+    fn find_string(&self, value: &str, hash: u32) -> Option<ObjRef<String>> {
+        return self.strings.find_string(hash, |key| self.deref(key) == value);
+    }
+
+    pub fn deref<T: ObjTrait + 'static>(&self, reference: ObjRef<T>) -> &T {
+        match self.try_deref(reference) {
+            Ok(obj) => obj,
+            Err(error) => panic!("Invalid object reference {}: {:?}", reference.index, error),
+        }
+    }
+
+    pub fn deref_mut<T: ObjTrait + 'static>(&mut self, reference: ObjRef<T>) -> &mut T {
+        let index: usize = reference.index;
+        let header: &mut ObjHeader = match self.objects.get_mut(index) {
+            Some(header) => header,
+            None => panic!("Invalid object reference {index}: {:?}", DerefError::OutOfBounds),
+        };
+
+        match header.obj.as_any_mut().downcast_mut() {
+            Some(obj) => return obj,
+            None => panic!("Invalid object reference {index}: {:?}", DerefError::TypeMismatch),
+        }
+    }
+
+    pub fn try_deref<T: ObjTrait + 'static>(&self, reference: ObjRef<T>) -> Result<&T, DerefError> {
+        let header: &ObjHeader = match self.objects.get(reference.index) {
+            Some(header) => header,
+            None => return Err(DerefError::OutOfBounds),
+        };
+
+        return match header.obj.as_any().downcast_ref() {
+            Some(obj) => Ok(obj),
+            None => Err(DerefError::TypeMismatch),
+        };
     }
 }
 
 struct ObjHeader {
     size: usize,
+    // An interned string's hash, for tables; zero for every other object.
+    hash: u32,
     obj: Box<dyn ObjTrait>,
 }
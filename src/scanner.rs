@@ -24,6 +24,8 @@ pub enum TokenType {
     LessEqual,
     Greater,
     GreaterEqual,
+    DotDot,
+    DotDotEqual,
 
     // Keywords
     Var,
@@ -32,7 +34,9 @@ pub enum TokenType {
     This,
     Super,
     If,
+    In,
     Else,
+    Do,
     For,
     While,
     Return,
@@ -57,27 +61,67 @@ pub struct Token<'a> {
     pub token_type: TokenType,
     pub lexeme: &'a str,
     pub line: usize,
+    pub column: usize,
 }
 
 pub struct Scanner<'a> {
     pub start: Chars<'a>,
     pub current: Chars<'a>,
     pub line: usize,
+    pub column: usize,
+    pub tab_width: usize,
+    pub stop_on_error: bool,
+    pub print_keyword: bool,
+    start_column: usize,
+    exhausted: bool,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Scanner {
+        // Some editors start UTF-8 files with a byte order mark.
+        let source: &'a str = source.strip_prefix('\u{FEFF}').unwrap_or(source);
+
         return Scanner {
             start: source.chars(),
             current: source.chars(),
             line: 1,
+            column: 1,
+            tab_width: 1,
+            stop_on_error: false,
+            print_keyword: true,
+            start_column: 1,
+            exhausted: false,
         };
     }
 
+    // Starts over on `source` from line 1, keeping settings such as `tab_width`, so that a driver
+    // like the REPL can scan one input after another with the same scanner.
+    pub fn reset(&mut self, source: &'a str) -> () {
+        let source: &'a str = source.strip_prefix('\u{FEFF}').unwrap_or(source);
+
+        self.start = source.chars();
+        self.current = source.chars();
+        self.line = 1;
+        self.column = 1;
+        self.start_column = 1;
+        self.exhausted = false;
+    }
+
+    pub fn at_end(&self) -> bool {
+        return self.is_at_end();
+    }
+
+    // The line the scanner is on now, which is past the last token once whitespace after it has
+    // been skipped.
+    pub fn current_line(&self) -> usize {
+        return self.line;
+    }
+
     pub fn scan_token(&mut self) -> Token<'a> {
         self.skip_whitespace();
 
         self.start = self.current.clone();
+        self.start_column = self.column;
 
         if self.is_at_end() {
             return self.make_token(TokenType::Eof);
@@ -98,7 +142,15 @@ impl<'a> Scanner<'a> {
             ')' => return self.make_token(TokenType::RightParen),
             '{' => return self.make_token(TokenType::LeftBrace),
             '}' => return self.make_token(TokenType::RightBrace),
-            '.' => return self.make_token(TokenType::Dot),
+            '.' => (
+                if !self.match_character('.') {
+                    return self.make_token(TokenType::Dot);
+                } else if self.match_character('=') {
+                    return self.make_token(TokenType::DotDotEqual);
+                } else {
+                    return self.make_token(TokenType::DotDot);
+                }
+            ),
             ',' => return self.make_token(TokenType::Comma),
             ';' => return self.make_token(TokenType::Semicolon),
             '+' => return self.make_token(TokenType::Plus),
@@ -133,7 +185,16 @@ impl<'a> Scanner<'a> {
                     return self.make_token(TokenType::Greater);
                 }
             ),
-            '"' => return self.string(),
+            '"' => (
+                if self.current.as_str().starts_with("\"\"") {
+                    self.advance();
+                    self.advance();
+
+                    return self.triple_quoted_string();
+                } else {
+                    return self.string();
+                }
+            ),
             _ => (),
         }
 
@@ -145,15 +206,14 @@ impl<'a> Scanner<'a> {
 
         self.current.next();
 
-        return previous;
-    }
-
-    fn check_keyword(&self, start: usize, rest: &str, token_type: TokenType) -> TokenType {
-        if &self.lexeme()[start..] == rest {
-            return token_type;
+        match previous {
+            '\n' => self.column = 1,
+            '\r' if self.peek() != '\n' => self.column = 1,
+            '\t' => self.column += self.tab_width,
+            _ => self.column += 1,
         }
 
-        return TokenType::Identifier;
+        return previous;
     }
 
     fn error_token(&mut self, message: &'static str) -> Token<'a> {
@@ -161,6 +221,7 @@ impl<'a> Scanner<'a> {
             token_type: TokenType::Error,
             lexeme: message,
             line: self.line,
+            column: self.start_column,
         };
     }
 
@@ -173,41 +234,27 @@ impl<'a> Scanner<'a> {
     }
 
     fn identifier_type(&self) -> TokenType {
-        match self.start.as_str().as_bytes()[0] as char {
-            'a' => return self.check_keyword(1, "nd", TokenType::And),
-            'c' => return self.check_keyword(1, "lass", TokenType::Class),
-            'e' => return self.check_keyword(1, "lse", TokenType::Else),
-            'f' => {
-                if self.start.as_str().len() > 1 {
-                    match self.start.as_str().as_bytes()[1] as char {
-                        'a' => return self.check_keyword(2, "lse", TokenType::False),
-                        'o' => return self.check_keyword(2, "r", TokenType::For),
-                        'u' => return self.check_keyword(2, "n", TokenType::Fun),
-                        _ => (),
-                    }
-                }
-            },
-            'i' => return self.check_keyword(1, "f", TokenType::If),
-            'n' => return self.check_keyword(1, "il", TokenType::Nil),
-            'o' => return self.check_keyword(1, "r", TokenType::Or),
-            'p' => return self.check_keyword(1, "rint", TokenType::Print),
-            'r' => return self.check_keyword(1, "eturn", TokenType::Return),
-            's' => return self.check_keyword(1, "uper", TokenType::Super),
-            't' => {
-                if self.start.as_str().len() > 1 {
-                    match self.start.as_str().as_bytes()[1] as char {
-                        'h' => return self.check_keyword(2, "is", TokenType::This),
-                        'r' => return self.check_keyword(2, "ue", TokenType::True),
-                        _ => (),
-                    }
-                }
-            },
-            'v' => return self.check_keyword(1, "ar", TokenType::Var),
-            'w' => return self.check_keyword(1, "hile", TokenType::While),
-            _ => (),
+        match self.lexeme() {
+            "and" => return TokenType::And,
+            "class" => return TokenType::Class,
+            "do" => return TokenType::Do,
+            "else" => return TokenType::Else,
+            "false" => return TokenType::False,
+            "for" => return TokenType::For,
+            "fun" => return TokenType::Fun,
+            "if" => return TokenType::If,
+            "in" => return TokenType::In,
+            "nil" => return TokenType::Nil,
+            "or" => return TokenType::Or,
+            "print" if self.print_keyword => return TokenType::Print,
+            "return" => return TokenType::Return,
+            "super" => return TokenType::Super,
+            "this" => return TokenType::This,
+            "true" => return TokenType::True,
+            "var" => return TokenType::Var,
+            "while" => return TokenType::While,
+            _ => return TokenType::Identifier,
         }
-
-        return TokenType::Identifier
     }
 
     fn is_alpha(&self, character: char) -> bool {
@@ -224,6 +271,11 @@ impl<'a> Scanner<'a> {
         return character >= '0' && character <= '9';
     }
 
+    // Lines end in "\n", "\r\n" or a lone "\r"; the "\r" of a pair is skipped as whitespace.
+    fn is_line_break(&self) -> bool {
+        return self.peek() == '\n' || (self.peek() == '\r' && self.peek_next() != '\n');
+    }
+
     fn lexeme(&self) -> &'a str {
         let end: usize = self.start.as_str().len() - self.current.as_str().len();
         &self.start.as_str()[0..end]
@@ -234,6 +286,7 @@ impl<'a> Scanner<'a> {
             token_type: token_type,
             lexeme: &self.lexeme(),
             line: self.line,
+            column: self.start_column,
         };
     }
 
@@ -251,7 +304,15 @@ impl<'a> Scanner<'a> {
             self.advance();
         }
 
-        if self.peek() == '.' && self.is_digit(self.peek_next()) {
+        // A trailing dot (`5.`) is rejected outright rather than left for the parser to trip over,
+        // unless a name follows it, as in `5.floor()`, or it starts a range, as in `1..3`.
+        if self.peek() == '.' && !self.is_alpha(self.peek_next()) && self.peek_next() != '.' {
+            if !self.is_digit(self.peek_next()) {
+                self.advance();
+
+                return self.error_token("Expected digit after decimal point.");
+            }
+
             self.advance();
 
             while self.is_digit(self.peek()) {
@@ -278,19 +339,43 @@ impl<'a> Scanner<'a> {
         return self.current.as_str().as_bytes()[1] as char;
     }
 
+    // Scans up to and past the '}' that closes an interpolation, returning false if the source
+    // ends first. The string's own start is kept for its token.
+    fn skip_interpolation(&mut self) -> bool {
+        let (start, start_column): (Chars<'a>, usize) = (self.start.clone(), self.start_column);
+        let mut depth: usize = 0;
+
+        let closed: bool = loop {
+            match self.scan_token().token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth == 0 => break true,
+                TokenType::RightBrace => depth -= 1,
+                TokenType::Eof => break false,
+                _ => (),
+            }
+        };
+
+        self.start = start;
+        self.start_column = start_column;
+
+        return closed;
+    }
+
     fn skip_whitespace(&mut self) -> () {
         loop {
+            if self.is_line_break() {
+                self.line += 1;
+                self.advance();
+                continue;
+            }
+
             match self.peek() {
                 ' ' | '\r' | '\t' => {
                     self.advance();
                 }
-                '\n' => {
-                    self.line += 1;
-                    self.advance();
-                }
                 '/' => {
                     if self.peek_next() == '/' {
-                        while self.peek() != '\n' && !self.is_at_end() {
+                        while !self.is_line_break() && !self.is_at_end() {
                             self.advance();
                         }
                     } else {
@@ -302,13 +387,32 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    // An interpolation is scanned as the tokens it holds, so that quotes inside it start and end
+    // string literals of its own rather than this one.
     fn string(&mut self) -> Token<'a> {
+        let line: usize = self.line;
+        let mut escaped: bool = false;
+
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
+            if !escaped && self.current.as_str().starts_with("${") {
+                self.advance();
+                self.advance();
+
+                if !self.skip_interpolation() {
+                    let mut token: Token<'a> = self.error_token("Unterminated interpolation.");
+                    token.line = line;
+
+                    return token;
+                }
+
+                continue;
+            }
+
+            if self.is_line_break() {
                 self.line += 1;
             }
 
-            self.advance();
+            escaped = self.advance() == '\\';
         }
 
         if self.is_at_end() {
@@ -319,4 +423,49 @@ impl<'a> Scanner<'a> {
 
         return self.make_token(TokenType::String);
     }
+
+    fn triple_quoted_string(&mut self) -> Token<'a> {
+        let line: usize = self.line;
+
+        while !self.current.as_str().starts_with("\"\"\"") && !self.is_at_end() {
+            if self.is_line_break() {
+                self.line += 1;
+            }
+
+            self.advance();
+        }
+
+        if self.is_at_end() {
+            let mut token: Token<'a> = self.error_token("Unterminated String");
+            token.line = line;
+
+            return token;
+        }
+
+        self.advance();
+        self.advance();
+        self.advance();
+
+        return self.make_token(TokenType::String);
+    }
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.exhausted {
+            return None;
+        }
+
+        let token: Token<'a> = self.scan_token();
+
+        match token.token_type {
+            TokenType::Eof => self.exhausted = true,
+            TokenType::Error if self.stop_on_error => self.exhausted = true,
+            _ => (),
+        }
+
+        return Some(token);
+    }
 }
@@ -0,0 +1,228 @@
+use object::ObjRef;
+
+const MAX_LOAD_NUMERATOR: usize = 3;
+const MAX_LOAD_DENOMINATOR: usize = 4;
+const MIN_CAPACITY: usize = 8;
+
+// FNV-1a, which is cheap for the short names and literals that make up most interned strings.
+pub fn hash_string(value: &str) -> u32 {
+    let mut hash: u32 = 2166136261;
+
+    for byte in value.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(16777619);
+    }
+
+    return hash;
+}
+
+enum Entry<V> {
+    Empty,
+    // Left behind by a delete so that probe sequences running through the slot stay unbroken.
+    Tombstone,
+    Full { key: ObjRef<String>, hash: u32, value: V },
+}
+
+// An open-addressing hash table keyed by interned strings. Keys are compared by reference, so
+// callers pass the string's hash, which the allocator caches, rather than the table hashing the
+// contents on every lookup.
+pub struct Table<V> {
+    entries: Vec<Entry<V>>,
+    // Full entries and tombstones both count, since both lengthen probe sequences.
+    used: usize,
+    len: usize,
+}
+
+impl<V> Table<V> {
+    pub fn new() -> Table<V> {
+        return Table {
+            entries: Vec::new(),
+            used: 0,
+            len: 0,
+        };
+    }
+
+    // Room for `capacity` entries without growing.
+    pub fn with_capacity(capacity: usize) -> Table<V> {
+        let mut table: Table<V> = Table::new();
+        table.resize(Table::<V>::capacity_for(capacity));
+
+        return table;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    pub fn clear(&mut self) -> () {
+        for entry in self.entries.iter_mut() {
+            *entry = Entry::Empty;
+        }
+
+        self.used = 0;
+        self.len = 0;
+    }
+
+    pub fn get(&self, key: ObjRef<String>, hash: u32) -> Option<&V> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        match &self.entries[self.find_entry(key, hash)] {
+            Entry::Full { value, .. } => return Some(value),
+            _ => return None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: ObjRef<String>, hash: u32) -> Option<&mut V> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let index: usize = self.find_entry(key, hash);
+        match &mut self.entries[index] {
+            Entry::Full { value, .. } => return Some(value),
+            _ => return None,
+        }
+    }
+
+    pub fn contains_key(&self, key: ObjRef<String>, hash: u32) -> bool {
+        return self.get(key, hash).is_some();
+    }
+
+    // Returns whether the key is new.
+    pub fn set(&mut self, key: ObjRef<String>, hash: u32, value: V) -> bool {
+        if (self.used + 1) * MAX_LOAD_DENOMINATOR > self.entries.len() * MAX_LOAD_NUMERATOR {
+            self.resize((self.entries.len() * 2).max(MIN_CAPACITY));
+        }
+
+        let index: usize = self.find_entry(key, hash);
+        let is_new: bool = !matches!(self.entries[index], Entry::Full { .. });
+
+        if is_new {
+            // Reusing a tombstone doesn't make the table any fuller.
+            if let Entry::Empty = self.entries[index] {
+                self.used += 1;
+            }
+            self.len += 1;
+        }
+
+        self.entries[index] = Entry::Full { key, hash, value };
+
+        return is_new;
+    }
+
+    // Returns whether the key was present.
+    pub fn delete(&mut self, key: ObjRef<String>, hash: u32) -> bool {
+        if self.entries.is_empty() {
+            return false;
+        }
+
+        let index: usize = self.find_entry(key, hash);
+        if !matches!(self.entries[index], Entry::Full { .. }) {
+            return false;
+        }
+
+        self.entries[index] = Entry::Tombstone;
+        self.len -= 1;
+
+        return true;
+    }
+
+    // Finds a key by its contents rather than its reference, which is how the interner looks up
+    // a string it may not have a reference for yet. `matches` compares a candidate's contents.
+    pub fn find_string(
+        &self,
+        hash: u32,
+        matches: impl Fn(ObjRef<String>) -> bool,
+    ) -> Option<ObjRef<String>> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let mask: usize = self.entries.len() - 1;
+        let mut index: usize = hash as usize & mask;
+
+        loop {
+            match &self.entries[index] {
+                Entry::Empty => return None,
+                Entry::Full { key, hash: entry_hash, .. }
+                    if *entry_hash == hash && matches(*key) => return Some(*key),
+                _ => (),
+            }
+
+            index = (index + 1) & mask;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ObjRef<String>, &V)> {
+        return self.entries.iter().filter_map(|entry| match entry {
+            Entry::Full { key, value, .. } => Some((*key, value)),
+            _ => None,
+        });
+    }
+
+    fn capacity_for(len: usize) -> usize {
+        let needed: usize = len * MAX_LOAD_DENOMINATOR / MAX_LOAD_NUMERATOR + 1;
+
+        return needed.next_power_of_two().max(MIN_CAPACITY);
+    }
+
+    // The slot holding `key`, or else the slot it should go in: the first tombstone passed on
+    // the way, or the empty slot that ended the probe. The table must not be empty.
+    fn find_entry(&self, key: ObjRef<String>, hash: u32) -> usize {
+        let mask: usize = self.entries.len() - 1;
+        let mut index: usize = hash as usize & mask;
+        let mut tombstone: Option<usize> = None;
+
+        loop {
+            match &self.entries[index] {
+                Entry::Empty => return tombstone.unwrap_or(index),
+                Entry::Tombstone => {
+                    tombstone.get_or_insert(index);
+                },
+                Entry::Full { key: entry_key, hash: entry_hash, .. } if *entry_key == key => {
+                    // A caller passing the wrong hash would only be caught by luck otherwise.
+                    debug_assert_eq!(*entry_hash, hash, "key found under a different hash");
+                    return index;
+                },
+                Entry::Full { .. } => (),
+            }
+
+            index = (index + 1) & mask;
+        }
+    }
+
+    // Tombstones are dropped along the way.
+    fn resize(&mut self, capacity: usize) -> () {
+        let mut entries: Vec<Entry<V>> = Vec::with_capacity(capacity);
+        entries.resize_with(capacity, || Entry::Empty);
+
+        let old: Vec<Entry<V>> = std::mem::replace(&mut self.entries, entries);
+        self.used = 0;
+        self.len = 0;
+
+        for entry in old {
+            if let Entry::Full { key, hash, value } = entry {
+                let index: usize = self.find_entry(key, hash);
+                self.entries[index] = Entry::Full { key, hash, value };
+                self.used += 1;
+                self.len += 1;
+            }
+        }
+    }
+}
+
+impl<V: Clone> Table<V> {
+    pub fn add_all(&self, to: &mut Table<V>) -> () {
+        for entry in self.entries.iter() {
+            if let Entry::Full { key, hash, value } = entry {
+                to.set(*key, *hash, value.clone());
+            }
+        }
+    }
+}
@@ -1,28 +1,128 @@
-use object::ObjRef;
+use std::fmt;
 
-#[derive(Clone, Copy, PartialEq)]
+use natives::Native;
+use object::{Buffer, ObjAllocator, ObjRef, Range};
+use velox::VeloxOptions;
+
+// Value deliberately has no PartialEq: `equals` is the language's `==` and `identical` is the
+// stricter comparison for deduplication and hashing.
+#[derive(Clone, Copy, Debug)]
 pub enum Value {
     Nil,
     Bool(bool),
     Number(f64),
     String(ObjRef<String>),
+    Range(ObjRef<Range>),
+    Buffer(ObjRef<Buffer>),
+    Native(&'static Native),
 }
 
 impl Value {
-    pub fn print(&self) -> () {
+    pub fn formatter<'a>(&'a self, allocator: &'a ObjAllocator) -> ValueFormatter<'a> {
+        return ValueFormatter { value: self, allocator };
+    }
+
+    pub fn stringify(&self, allocator: &ObjAllocator) -> String {
         match self {
-            Value::Nil => print!("nil"),
-            Value::Bool(value) => print!("{value}"),
-            Value::Number(value) => print!("{value}"),
-            Value::String(reference) => print!("Some String"), // TODO: Update to support lookups
+            Value::Nil => "nil".to_owned(),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(reference) => allocator.deref(*reference).clone(),
+            Value::Range(reference) => {
+                let range: &Range = allocator.deref(*reference);
+                let operator: &str = if range.inclusive { "..=" } else { ".." };
+
+                format!("{}{operator}{}", range.start, range.end)
+            },
+            Value::Buffer(reference) => allocator.deref(*reference).contents.clone(),
+            Value::Native(native) => format!("<native fn {}>", native.name),
+        }
+    }
+
+    // How `print` shows a value: like `stringify`, but numbers honour `--precision` and nil is
+    // shown as the embedder's `nil_text`.
+    pub fn display(&self, allocator: &ObjAllocator, options: &VeloxOptions) -> String {
+        match (self, options.precision) {
+            (Value::Number(number), Some(precision)) => return format!("{number:.precision$}"),
+            (Value::Nil, _) => return options.nil_text.clone(),
+            _ => return self.stringify(allocator),
         }
     }
 
-    pub fn is_falsy(&self) -> bool {
+    pub fn type_name(&self) -> &'static str {
         match self {
-            Value::Bool(value) => !value,
-            Value::Nil => true,
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Range(_) => "range",
+            Value::Buffer(_) => "buffer",
+            Value::Native(_) => "native",
+        }
+    }
+
+    // IEEE semantics: NaN is never equal to itself and 0 equals -0.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            // Every string value is interned (the Runner asserts this on push), so equal contents
+            // always share a reference.
+            (Value::String(a), Value::String(b)) => a == b,
+            // Ranges and buffers are objects and compare by identity.
+            (Value::Range(a), Value::Range(b)) => a == b,
+            (Value::Buffer(a), Value::Buffer(b)) => a == b,
+            (Value::Native(a), Value::Native(b)) => std::ptr::eq(*a, *b),
             _ => false,
         }
     }
+
+    // Bitwise semantics: NaN is identical to itself and 0 is distinct from -0.
+    pub fn identical(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.to_bits() == b.to_bits(),
+            _ => self.equals(other),
+        }
+    }
+
+    // Only nil and false are falsy: 0, "", and empty ranges and buffers are all truthy. Every
+    // conditional goes through here, so the rule is the same everywhere.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            Value::Nil => false,
+            _ => true,
+        }
+    }
+}
+
+// Formats a value with access to the allocator: Display shows it as `print` would, and Debug
+// adds the contents of strings to their references.
+pub struct ValueFormatter<'a> {
+    value: &'a Value,
+    allocator: &'a ObjAllocator,
+}
+
+impl<'a> fmt::Display for ValueFormatter<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str(&self.value.stringify(self.allocator));
+    }
+}
+
+impl<'a> fmt::Debug for ValueFormatter<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Value::String(reference) => {
+                let contents: &String = self.allocator.deref(*reference);
+
+                return formatter
+                    .debug_tuple("String")
+                    .field(reference)
+                    .field(contents)
+                    .finish();
+            },
+            value => return fmt::Debug::fmt(value, formatter),
+        }
+    }
 }
@@ -0,0 +1,69 @@
+use std::{
+    hash::{Hash, Hasher},
+    mem,
+    ptr,
+};
+
+use value::Value;
+
+// A Value that can key a hash map. Keys are equal exactly when `Value::equals` says so, except
+// that every NaN is the same key: `==` never matches NaN, but a key must equal itself. Numbers
+// hash by bit pattern after folding -0 into 0 and every NaN into one, and strings by reference,
+// which is enough because every string is interned.
+#[derive(Clone, Copy)]
+pub struct ValueKey(Value);
+
+impl ValueKey {
+    // Buffers are mutable, so their contents can change after they are hashed.
+    pub fn new(value: Value) -> Result<ValueKey, String> {
+        match value {
+            Value::Buffer(_) => {
+                return Err(format!("Value of type {} can't be used as a key.", value.type_name()));
+            },
+            _ => return Ok(ValueKey(value)),
+        }
+    }
+
+    pub fn value(&self) -> Value {
+        return self.0;
+    }
+}
+
+impl PartialEq for ValueKey {
+    fn eq(&self, other: &ValueKey) -> bool {
+        match (self.0, other.0) {
+            (Value::Number(a), Value::Number(b)) => return number_bits(a) == number_bits(b),
+            (a, b) => return a.equals(&b),
+        }
+    }
+}
+
+impl Eq for ValueKey {}
+
+impl Hash for ValueKey {
+    fn hash<H: Hasher>(&self, state: &mut H) -> () {
+        mem::discriminant(&self.0).hash(state);
+
+        match self.0 {
+            Value::Nil => (),
+            Value::Bool(value) => value.hash(state),
+            Value::Number(value) => number_bits(value).hash(state),
+            Value::String(reference) => reference.hash(state),
+            Value::Range(reference) => reference.hash(state),
+            Value::Native(native) => ptr::hash(native, state),
+            Value::Buffer(_) => unreachable!("buffers are rejected by ValueKey::new"),
+        }
+    }
+}
+
+fn number_bits(value: f64) -> u64 {
+    if value == 0.0 {
+        return 0;
+    }
+
+    if value.is_nan() {
+        return f64::NAN.to_bits();
+    }
+
+    return value.to_bits();
+}
@@ -1,39 +1,322 @@
 use std::{
     env,
-    fs::File,
-    io::{self, Read, Write},
-    iter::FromIterator,
+    fs::{self, File},
+    io::{self, BufRead, IsTerminal, Read, Write},
     process::exit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
 };
 
-use vm::{InterpretResult, VM};
+use compiler::{Diagnostic, Severity};
+use debugger::StdinDebugger;
+use vm::{InterpretResult, STACK_MAX, VM, VmSnapshot};
+
+#[derive(Clone)]
+pub struct VeloxOptions {
+    pub print_stats: bool,
+    pub print_timings: bool,
+    pub trace_execution: bool,
+    pub dump_code: bool,
+    pub check_only: bool,
+    pub json_diagnostics: bool,
+    pub deny_warnings: bool,
+    pub show_types: bool,
+    pub print_as_function: bool,
+    pub strict_conditions: bool,
+    pub optimize: bool,
+    pub precision: Option<usize>,
+    // How `print` shows nil; an embedder may prefer "" when filling in templates.
+    pub nil_text: String,
+    // Shown once when the REPL starts on a terminal. Embedders get none unless they set one; the
+    // rvelox binary shows its own unless run with `--quiet`.
+    pub banner: Option<String>,
+    pub prompt: String,
+    // Shown instead of `prompt` while a statement continues onto the next line.
+    pub continuation_prompt: String,
+    // Compilation stops after this many errors; zero sets no limit.
+    pub max_errors: usize,
+    pub stack_size: usize,
+    pub max_stack_size: Option<usize>,
+    pub globals_capacity: Option<usize>,
+    pub strings_capacity: Option<usize>,
+    pub max_instructions: Option<usize>,
+}
+
+impl Default for VeloxOptions {
+    fn default() -> Self {
+        Self {
+            print_stats: false,
+            print_timings: false,
+            trace_execution: false,
+            dump_code: false,
+            check_only: false,
+            json_diagnostics: false,
+            deny_warnings: false,
+            show_types: false,
+            print_as_function: false,
+            strict_conditions: false,
+            optimize: false,
+            precision: None,
+            nil_text: "nil".to_owned(),
+            banner: None,
+            prompt: "> ".to_owned(),
+            continuation_prompt: "... ".to_owned(),
+            max_errors: 20,
+            stack_size: STACK_MAX,
+            max_stack_size: None,
+            globals_capacity: None,
+            strings_capacity: None,
+            max_instructions: None,
+        }
+    }
+}
+
+impl VeloxOptions {
+    // Defaults overridden by RVELOX_* environment variables. A malformed value is reported and
+    // leaves the default in place.
+    pub fn from_env() -> VeloxOptions {
+        let mut options: VeloxOptions = VeloxOptions::default();
+
+        if let Some(trace) = VeloxOptions::env_flag("RVELOX_TRACE") {
+            options.trace_execution = trace;
+        }
+
+        if let Some(dump) = VeloxOptions::env_flag("RVELOX_DUMP_CODE") {
+            options.dump_code = dump;
+        }
+
+        if let Ok(value) = env::var("RVELOX_STACK_SIZE") {
+            match value.parse() {
+                Ok(stack_size) => options.stack_size = stack_size,
+                Err(_) => eprintln!("Ignoring invalid RVELOX_STACK_SIZE \"{value}\"."),
+            }
+        }
+
+        return options;
+    }
+
+    // Sets the option a command-line flag names. Anything that names no option is left to the
+    // caller with Ok(false); a malformed value is an error.
+    pub fn set_flag(&mut self, flag: &str) -> Result<bool, String> {
+        match flag {
+            "--stats" => self.print_stats = true,
+            "--time" => self.print_timings = true,
+            "--trace" => self.trace_execution = true,
+            "--dump-code" => self.dump_code = true,
+            "--check" => self.check_only = true,
+            "--format=json" => self.json_diagnostics = true,
+            "--format=text" => self.json_diagnostics = false,
+            "--deny-warnings" => self.deny_warnings = true,
+            "--print-as-function" => self.print_as_function = true,
+            "--strict-conditions" => self.strict_conditions = true,
+            "--opt" => self.optimize = true,
+            "--quiet" => self.banner = None,
+            flag if flag.starts_with("--precision=") => match flag["--precision=".len()..].parse() {
+                Ok(precision) => self.precision = Some(precision),
+                Err(_) => return Err(format!("Invalid precision \"{flag}\".")),
+            },
+            flag if flag.starts_with("--max-errors=") => {
+                self.max_errors = VeloxOptions::parse_count(flag, "--max-errors=")?;
+            },
+            flag if flag.starts_with("--stack-size=") => {
+                self.stack_size = VeloxOptions::parse_count(flag, "--stack-size=")?;
+            },
+            _ => return Ok(false),
+        }
+
+        return Ok(true);
+    }
+
+    fn env_flag(name: &str) -> Option<bool> {
+        match env::var(name).as_deref() {
+            Err(_) => return None,
+            Ok("1") | Ok("true") => return Some(true),
+            Ok("0") | Ok("false") | Ok("") => return Some(false),
+            Ok(value) => {
+                eprintln!("Ignoring invalid {name} \"{value}\".");
+                return None;
+            },
+        }
+    }
+
+    fn parse_count(flag: &str, prefix: &str) -> Result<usize, String> {
+        match flag[prefix.len()..].parse() {
+            Ok(count) => return Ok(count),
+            Err(_) => return Err(format!("Invalid count \"{flag}\".")),
+        }
+    }
+}
+
+// The SIGINT handler can only reach statics: the flag of the VM the prompt runs, and whether a
+// line is running at all.
+#[cfg(unix)]
+static INTERRUPT_FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" {
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    fn write(fd: i32, buffer: *const u8, count: usize) -> isize;
+    fn _exit(status: i32) -> !;
+}
+
+#[cfg(unix)]
+const SIGINT: i32 = 2;
+
+// Interrupts the running line. At an idle prompt the first Ctrl-C only arms the flag, which the
+// next line clears, and a second one exits.
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: i32) {
+    let flag: &Arc<AtomicBool> = match INTERRUPT_FLAG.get() {
+        Some(flag) => flag,
+        None => return,
+    };
+    let armed: bool = flag.swap(true, Ordering::Relaxed);
+
+    if !RUNNING.load(Ordering::Relaxed) {
+        if armed {
+            unsafe { _exit(130) };
+        }
+
+        let message: &[u8] = b"\n(Press Ctrl-C again or Ctrl-D to exit.)\n";
+        unsafe { write(2, message.as_ptr(), message.len()) };
+    }
+}
 
 pub struct Velox {
     vm: VM,
+    snapshot: Option<VmSnapshot>,
 }
 
 impl Velox {
     pub fn new() -> Velox {
         return Velox {
             vm: VM::new(),
+            snapshot: None,
+        };
+    }
+
+    pub fn with_options(options: VeloxOptions) -> Velox {
+        return Velox {
+            vm: VM::with_options(options),
+            snapshot: None,
         };
     }
 
     pub fn main(&mut self) -> () {
-        let args: Vec<String> = Vec::from_iter(env::args().skip(1));
+        let mut options: VeloxOptions = VeloxOptions::from_env();
+        options.banner = Some("rvelox, press Ctrl-D to exit.".to_owned());
+
+        let mut debug: bool = false;
+        let mut args: Vec<String> = Vec::new();
+
+        for arg in env::args().skip(1) {
+            if arg == "--debug" {
+                debug = true;
+                continue;
+            }
+
+            match options.set_flag(&arg) {
+                Ok(true) => (),
+                Ok(false) => args.push(arg),
+                Err(message) => {
+                    eprintln!("{message}");
+                    exit(64);
+                },
+            }
+        }
+
+        self.vm = VM::with_options(options);
+
+        if debug {
+            self.vm.set_debugger(Box::new(StdinDebugger::new()));
+        }
 
         match args.len() {
             0 => self.run_prompt(),
             1 => self.run_file(&args[0]),
             _ => {
-                eprintln!("Usage: rvelox [path]");
+                eprintln!(
+                    "Usage: rvelox [--check] [--format=text|json] [--stats] [--time] \
+                     [--trace] [--dump-code] [--debug] [--deny-warnings] [--print-as-function] \
+                     [--strict-conditions] [--opt] [--quiet] [--precision=<digits>] [--max-errors=<n>] \
+                     [--stack-size=<n>] [path]"
+                );
                 exit(64);
             },
         }
     }
 
-    fn interpret(&mut self, source: &str) -> InterpretResult {
-        return self.vm.interpret(source);
+    fn check(&mut self, source: &str, path: &str) -> () {
+        let diagnostics: Vec<Diagnostic> = self.vm.check_named(source, path);
+        let json: bool = self.vm.options().json_diagnostics;
+        let deny_warnings: bool = self.vm.options().deny_warnings;
+
+        for diagnostic in diagnostics.iter() {
+            if json {
+                println!("{}", diagnostic.to_json());
+            } else {
+                eprintln!("{}", diagnostic.render());
+            }
+        }
+
+        let failed: bool = diagnostics
+            .iter()
+            .any(|diagnostic| deny_warnings || diagnostic.severity == Severity::Error);
+
+        if failed {
+            exit(65);
+        }
+    }
+
+    // Ctrl-C only interrupts the prompt's own VM; running a file keeps the default, which exits.
+    #[cfg(unix)]
+    fn install_interrupt_handler(flag: Arc<AtomicBool>) -> () {
+        if INTERRUPT_FLAG.set(flag).is_ok() {
+            unsafe { signal(SIGINT, on_interrupt) };
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn install_interrupt_handler(_: Arc<AtomicBool>) -> () {}
+
+    // Runs a line or loaded file so that Ctrl-C interrupts it rather than arming the exit.
+    fn interruptible(&mut self, run: impl FnOnce(&mut VM) -> InterpretResult) -> () {
+        // A Ctrl-C pressed at the idle prompt must not stop the line that follows it.
+        self.vm.interrupt_flag().store(false, Ordering::Relaxed);
+
+        RUNNING.store(true, Ordering::Relaxed);
+        run(&mut self.vm);
+        RUNNING.store(false, Ordering::Relaxed);
+    }
+
+    // Runs a file in the current session, so its globals stay defined at the prompt. Errors are
+    // reported like any other line's and leave the REPL running.
+    fn load(&mut self, path: &str) -> () {
+        match fs::read_to_string(path) {
+            Ok(source) => self.interruptible(|vm| vm.interpret_named(&source, path)),
+            Err(_) => eprintln!("Could not read file \"{path}\"."),
+        }
+    }
+
+    fn run_command(&mut self, line: &str) -> () {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            [":types", "on"] => self.vm.options_mut().show_types = true,
+            [":types", "off"] => self.vm.options_mut().show_types = false,
+            [":reset"] => self.vm.reset(true),
+            [":save"] => self.snapshot = Some(self.vm.snapshot()),
+            [":restore"] => match &self.snapshot {
+                Some(snapshot) => self.vm.restore(snapshot),
+                None => eprintln!("No snapshot saved."),
+            },
+            [":load"] => eprintln!("Usage: :load <path>"),
+            [":load", ..] => self.load(line[":load".len()..].trim()),
+            _ => eprintln!("Unknown command '{line}'."),
+        }
     }
 
     fn run_file(&mut self, path: &String) -> () {
@@ -44,7 +327,11 @@ impl Velox {
         file.read_to_string(&mut source)
             .expect("Could not read file \"{path}\".");
 
-        match self.interpret(&source) {
+        if self.vm.options().check_only {
+            return self.check(&source, path);
+        }
+
+        match self.vm.interpret_named(&source, path) {
             InterpretResult::Ok => (),
             InterpretResult::CompileError => exit(65),
             InterpretResult::RuntimeError => exit(70),
@@ -52,17 +339,67 @@ impl Velox {
     }
 
     fn run_prompt(&mut self) -> () {
-        let mut lines = io::stdin().lines();
+        let interactive: bool = io::stdin().is_terminal();
+
+        if interactive {
+            Velox::install_interrupt_handler(self.vm.interrupt_flag());
+        }
+
+        self.repl(io::stdin().lock(), &mut io::stdout(), interactive);
+    }
+
+    // Reads statements and commands from `input` until it ends. The banner and prompts go to
+    // `output`, and only when `interactive`, so that piped output holds nothing but results.
+    // Input that stops mid-statement is continued on the next line; a blank line gives up on it
+    // and reports its errors.
+    pub fn repl(&mut self, input: impl BufRead, output: &mut dyn Write, interactive: bool) -> () {
+        let mut lines = input.lines();
+        let mut pending: String = String::new();
+
+        if interactive {
+            if let Some(banner) = &self.vm.options().banner {
+                writeln!(output, "{banner}").unwrap();
+            }
+        }
 
         loop {
-            print!("> ");
-            io::stdout().flush().unwrap();
+            if interactive {
+                let options: &VeloxOptions = self.vm.options();
+                let prompt: &str = match pending.is_empty() {
+                    true => &options.prompt,
+                    false => &options.continuation_prompt,
+                };
+
+                write!(output, "{prompt}").unwrap();
+                output.flush().unwrap();
+            }
 
             match lines.next() {
                 Some(line) => {
-                    self.interpret(&line.unwrap());
+                    let line: String = line.unwrap();
+
+                    if pending.is_empty() && line.starts_with(':') {
+                        self.run_command(&line);
+                        continue;
+                    }
+
+                    if !pending.is_empty() {
+                        pending.push('\n');
+                    }
+                    pending.push_str(&line);
+
+                    if !line.trim().is_empty() && self.vm.is_incomplete(&pending) {
+                        continue;
+                    }
+
+                    self.interruptible(|vm| vm.interpret_repl(&pending));
+                    pending.clear();
                 },
                 _ => {
+                    if !pending.is_empty() {
+                        self.interruptible(|vm| vm.interpret_repl(&pending));
+                    }
+
                     return
                 },
             }
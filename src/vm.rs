@@ -1,64 +1,421 @@
 use std::{
-    collections::HashMap,
     convert::TryInto,
+    io::{self, Write},
     slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use chunk::{Chunk, Op};
-use compiler::Compiler;
-use debug::DEBUG_TRACE_EXECUTION;
-use object::{ObjAllocator, ObjRef};
+use chunk::{source_location, Chunk, Op};
+use compiler::{Compiler, Diagnostic, Severity};
+use debugger::{DebugContext, Debugger};
+use globals::Globals;
+use methods::{self, Method};
+use natives::{self, Native};
+use object::{Buffer, ObjAllocator, ObjRef, Range};
 use value::Value;
+use velox::VeloxOptions;
 
+#[derive(Debug, PartialEq)]
 pub enum InterpretResult {
     Ok,
     CompileError,
     RuntimeError,
 }
 
-const STACK_MAX: usize = 256;
+pub const STACK_MAX: usize = 256;
+// The longest string a repeat may build, so that a huge count is an error rather than an abort.
+pub const STRING_LENGTH_MAX: usize = 1 << 30;
 
+// Snapshots copy string contents out of the allocator rather than holding ObjRefs, so they stay
+// valid across `reset` and can be restored into any VM.
+enum SnapshotValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Range(f64, f64, bool),
+    Buffer(String),
+    Native(&'static Native),
+}
+
+pub struct VmSnapshot {
+    globals: Vec<(String, SnapshotValue, bool)>,
+}
+
+// A VM is Send but not Sync: a host may hand it to another thread between calls to `interpret`
+// (behind a Mutex, say), but only one thread may use it at a time. After a RuntimeError the stack
+// is empty and the VM can run again; globals defined before the error are kept, and interned
+// strings stay valid until `reset` frees them.
 pub struct VM {
     stack: Vec<Value>,
     allocator: ObjAllocator,
+    globals: Globals,
+    options: VeloxOptions,
+    debugger: Option<Box<dyn Debugger>>,
+    max_stack_depth: usize,
+    interrupted: Arc<AtomicBool>,
+    // What the program prints, and its compile and runtime errors. Traces, disassembly and
+    // timings are tooling output and always go to stderr.
+    output: Box<dyn Write + Send>,
+    error_output: Box<dyn Write + Send>,
+}
+
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<VM>();
+};
+
+// The default output and error output. Going through print! and eprint!, rather than writing to
+// the handles, leaves the output of library tests to the test harness to capture.
+struct Printer {
+    to_stderr: bool,
+}
+
+impl Write for Printer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(bytes);
+
+        match self.to_stderr {
+            true => eprint!("{text}"),
+            false => print!("{text}"),
+        }
+
+        return Ok(bytes.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.to_stderr {
+            true => return io::stderr().flush(),
+            false => return io::stdout().flush(),
+        }
+    }
 }
 
 impl VM {
     pub fn new() -> VM {
+        return VM::with_options(VeloxOptions::default());
+    }
+
+    pub fn with_options(options: VeloxOptions) -> VM {
+        // Capacity hints only avoid rehashing while a large script defines its globals.
+        let allocator: ObjAllocator = options
+            .strings_capacity
+            .map_or_else(ObjAllocator::new, ObjAllocator::with_capacity);
+        let globals: Globals = options
+            .globals_capacity
+            .map_or_else(Globals::new, Globals::with_capacity);
+
         return VM {
-            stack: Vec::with_capacity(STACK_MAX),
-            allocator: ObjAllocator::new(),
+            stack: Vec::with_capacity(options.stack_size),
+            allocator,
+            globals,
+            options,
+            debugger: None,
+            max_stack_depth: 0,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            output: Box::new(Printer { to_stderr: false }),
+            error_output: Box::new(Printer { to_stderr: true }),
         };
     }
 
+    pub fn with_stack_size(stack_size: usize) -> VM {
+        return VM::with_options(VeloxOptions {
+            stack_size,
+            ..VeloxOptions::default()
+        });
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        return self.allocator.allocated_bytes();
+    }
+
+    pub fn max_stack_depth_reached(&self) -> usize {
+        return self.max_stack_depth;
+    }
+
+    // Setting the flag, from a signal handler or another thread, stops the running script with
+    // an "Interrupted." runtime error. The flag is cleared once the error is raised.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        return Arc::clone(&self.interrupted);
+    }
+
+    // Frozen globals are for values the host provides: scripts can read them but neither
+    // assign nor redefine them.
+    pub fn define_frozen_global(&mut self, name: &str, value: Value) -> () {
+        let reference: ObjRef<String> = self.allocator.intern(name.to_owned());
+        let hash: u32 = self.allocator.hash(reference);
+
+        self.globals.insert(reference, hash, value);
+        self.globals.freeze(reference, hash);
+    }
+
+    pub fn globals_iter(&self) -> impl Iterator<Item = (&str, Value)> {
+        return self.globals
+            .iter()
+            .map(move |(name, value)| (self.allocator.deref(*name).as_str(), *value));
+    }
+
     pub fn interpret(&mut self, source: &str) -> InterpretResult {
+        return self.run_source(source, None, false);
+    }
+
+    // Errors name `name`, usually the path the source was read from.
+    pub fn interpret_named(&mut self, source: &str, name: &str) -> InterpretResult {
+        return self.run_source(source, Some(name), false);
+    }
+
+    pub fn interpret_repl(&mut self, source: &str) -> InterpretResult {
+        return self.run_source(source, Some("<repl>"), true);
+    }
+
+    pub fn check(&mut self, source: &str) -> Vec<Diagnostic> {
+        return self.compile_diagnostics(source, None, false);
+    }
+
+    pub fn check_named(&mut self, source: &str, name: &str) -> Vec<Diagnostic> {
+        return self.compile_diagnostics(source, Some(name), false);
+    }
+
+    // Whether a prompt line only failed to compile because it stops early, as `print 1 +` or an
+    // unclosed block does, so that the next line may complete it.
+    pub fn is_incomplete(&mut self, source: &str) -> bool {
+        let errors: Vec<Diagnostic> = self
+            .compile_diagnostics(source, Some("<repl>"), true)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .collect();
+
+        let at_end = |diagnostic: &Diagnostic| diagnostic.location == " at end";
+
+        return !errors.is_empty() && errors.iter().all(at_end);
+    }
+
+    pub fn reset(&mut self, free_objects: bool) -> () {
+        self.stack.clear();
+        self.globals.clear();
+        self.max_stack_depth = 0;
+
+        // Every live reference is gone once the stack and globals are empty, so the whole
+        // allocator can be dropped at once.
+        if free_objects {
+            self.allocator = ObjAllocator::new();
+        }
+    }
+
+    pub fn snapshot(&self) -> VmSnapshot {
+        let globals: Vec<(String, SnapshotValue, bool)> = self.globals
+            .iter()
+            .map(|(name, value)| {
+                let snapshot: SnapshotValue = match value {
+                    Value::Nil => SnapshotValue::Nil,
+                    Value::Bool(value) => SnapshotValue::Bool(*value),
+                    Value::Number(value) => SnapshotValue::Number(*value),
+                    Value::String(reference) => {
+                        SnapshotValue::String(self.allocator.deref(*reference).clone())
+                    },
+                    Value::Range(reference) => {
+                        let range: &Range = self.allocator.deref(*reference);
+
+                        SnapshotValue::Range(range.start, range.end, range.inclusive)
+                    },
+                    Value::Buffer(reference) => {
+                        SnapshotValue::Buffer(self.allocator.deref(*reference).contents.clone())
+                    },
+                    Value::Native(native) => SnapshotValue::Native(native),
+                };
+
+                let frozen: bool = self.globals.is_frozen(*name, self.allocator.hash(*name));
+
+                (self.allocator.deref(*name).clone(), snapshot, frozen)
+            })
+            .collect();
+
+        return VmSnapshot { globals };
+    }
+
+    pub fn string_value(&mut self, value: &str) -> Value {
+        return Value::String(self.allocator.intern(value.to_owned()));
+    }
+
+    pub fn stringify(&self, value: &Value) -> String {
+        return value.stringify(&self.allocator);
+    }
+
+    pub fn restore(&mut self, snapshot: &VmSnapshot) -> () {
+        self.globals.clear();
+
+        for (name, value, frozen) in snapshot.globals.iter() {
+            let reference: ObjRef<String> = self.allocator.intern(name.clone());
+            let hash: u32 = self.allocator.hash(reference);
+            let value: Value = match value {
+                SnapshotValue::Nil => Value::Nil,
+                SnapshotValue::Bool(value) => Value::Bool(*value),
+                SnapshotValue::Number(value) => Value::Number(*value),
+                SnapshotValue::String(value) => Value::String(self.allocator.intern(value.clone())),
+                SnapshotValue::Range(start, end, inclusive) => {
+                    let range: Range = Range { start: *start, end: *end, inclusive: *inclusive };
+
+                    Value::Range(self.allocator.alloc(range))
+                },
+                SnapshotValue::Buffer(contents) => {
+                    Value::Buffer(self.allocator.alloc(Buffer { contents: contents.clone() }))
+                },
+                SnapshotValue::Native(native) => Value::Native(native),
+            };
+
+            self.globals.insert(reference, hash, value);
+
+            if *frozen {
+                self.globals.freeze(reference, hash);
+            }
+        }
+    }
+
+    pub fn set_debugger(&mut self, debugger: Box<dyn Debugger>) -> () {
+        self.debugger = Some(debugger);
+    }
+
+    pub fn set_error_output(&mut self, error_output: Box<dyn Write + Send>) -> () {
+        self.error_output = error_output;
+    }
+
+    pub fn set_output(&mut self, output: Box<dyn Write + Send>) -> () {
+        self.output = output;
+    }
+
+    pub fn options(&self) -> &VeloxOptions {
+        return &self.options;
+    }
+
+    pub fn options_mut(&mut self) -> &mut VeloxOptions {
+        return &mut self.options;
+    }
+
+    // Runs a chunk as is, without verifying it first.
+    pub fn run_chunk(&mut self, chunk: &Chunk) -> InterpretResult {
+        let mut runner: Runner = Runner::new(
+            &mut self.stack,
+            &mut self.allocator,
+            &mut self.globals,
+            &self.options,
+            &self.interrupted,
+            &mut *self.output,
+            &mut *self.error_output,
+            self.debugger.as_mut(),
+            chunk,
+        );
+
+        let run_start: Instant = Instant::now();
+        let result: InterpretResult = runner.run();
+        let run_time: Duration = run_start.elapsed();
+
+        if self.options.print_timings {
+            eprintln!("run time: {run_time:?}");
+        }
+        self.max_stack_depth = self.max_stack_depth.max(runner.max_stack_depth);
+
+        return result;
+    }
+
+    fn compile_diagnostics(
+        &mut self,
+        source: &str,
+        name: Option<&str>,
+        echo_expressions: bool,
+    ) -> Vec<Diagnostic> {
+        let mut chunk = Chunk::new();
+        let mut compiler = Compiler::new(source, &mut self.allocator, &mut chunk, echo_expressions);
+        if let Some(name) = name {
+            compiler.set_source_name(name);
+        }
+        compiler.set_print_as_function(self.options.print_as_function);
+        compiler.set_max_errors(self.options.max_errors);
+        compiler.set_optimize(self.options.optimize);
+        compiler.set_globals(&self.globals);
+
+        compiler.compile();
+
+        return compiler.diagnostics().to_vec();
+    }
+
+    fn run_source(
+        &mut self,
+        source: &str,
+        name: Option<&str>,
+        echo_expressions: bool,
+    ) -> InterpretResult {
         let mut chunk = Chunk::new();
-        let mut compiler = Compiler::new(source, &mut self.allocator, &mut chunk);
+        let mut compiler = Compiler::new(source, &mut self.allocator, &mut chunk, echo_expressions);
+        if let Some(name) = name {
+            compiler.set_source_name(name);
+        }
+        compiler.set_print_as_function(self.options.print_as_function);
+        compiler.set_max_errors(self.options.max_errors);
+        compiler.set_optimize(self.options.optimize);
+        compiler.set_globals(&self.globals);
+
+        // The whole source is compiled before anything runs, so a line that fails to compile
+        // never touches the stack or the globals.
+        let compile_start: Instant = Instant::now();
+        let compiled: bool = compiler.compile();
+        let compile_time: Duration = compile_start.elapsed();
 
-        if !compiler.compile() {
+        if self.options.print_timings {
+            eprintln!("compile time: {compile_time:?}");
+        }
+
+        for diagnostic in compiler.diagnostics() {
+            writeln!(self.error_output, "{}", diagnostic.render()).unwrap();
+        }
+
+        if !compiled || (self.options.deny_warnings && compiler.had_warning()) {
+            return InterpretResult::CompileError;
+        }
+
+        if self.options.dump_code {
+            chunk.dissassemble_chunk("code", &self.allocator);
+        }
+
+        if let Err(error) = chunk.verify() {
+            writeln!(self.error_output, "Invalid bytecode: {error:?}").unwrap();
             return InterpretResult::CompileError;
         }
 
-        return Runner::new(&mut self.stack, &mut self.allocator, &chunk).run();
+        if self.options.print_stats {
+            let mut statistics: String = String::new();
+            chunk.write_statistics(&mut statistics).unwrap();
+
+            write!(self.output, "{statistics}").unwrap();
+        }
+
+        return self.run_chunk(&chunk);
     }
 }
 
 macro_rules! binary_op {
     ($self:ident, $result_type:ident, $op:tt) => {
         {
-            let (b, a) = ($self.pop(), $self.pop());
+            let (b, a) = ($self.peek(0), $self.peek(1));
 
             match (&a, &b) {
                 (Value::Number(a), Value::Number(b)) => {
-                    $self.push(Value::$result_type(a $op b));
+                    $self.pop();
+                    *$self.top_mut() = Value::$result_type(a $op b);
 
                     None
                 }
                 _ => {
-                    $self.push(a);
-                    $self.push(b);
+                    let message: String = format!(
+                        "Operands must be numbers, got {} and {}.",
+                        a.type_name(),
+                        b.type_name(),
+                    );
 
-                    $self.runtime_error("Operands must be numbers.")
+                    $self.runtime_error(&message)
                 }
             }
 
@@ -68,43 +425,136 @@ macro_rules! binary_op {
 
 struct Runner<'a> {
     stack: &'a mut Vec<Value>,
-    allocator: &'a ObjAllocator,
+    allocator: &'a mut ObjAllocator,
+    globals: &'a mut Globals,
+    options: &'a VeloxOptions,
+    interrupted: &'a AtomicBool,
+    output: &'a mut dyn Write,
+    error_output: &'a mut dyn Write,
+    debugger: Option<&'a mut Box<dyn Debugger>>,
     chunk: &'a Chunk,
     ip: slice::Iter<'a, u8>,
-    globals: HashMap<ObjRef<String>, Value>,
+    // Where the running frame's slots start. Slot zero is reserved for the function being run,
+    // so that locals keep their encoding once there are functions; until then it holds nil.
+    frame_base: usize,
+    max_stack_depth: usize,
+    instruction_count: usize,
+    // Debug builds only: where the current instruction starts, whether it popped an empty stack,
+    // and a stand-in value for it to work on until the underflow is reported.
+    instruction_start: usize,
+    underflow: bool,
+    placeholder: Value,
 }
 
 impl<'a> Runner<'a> {
-    pub fn new(stack: &'a mut Vec<Value>, allocator: &'a ObjAllocator, chunk: &'a Chunk) -> Self {
+    pub fn new(
+        stack: &'a mut Vec<Value>,
+        allocator: &'a mut ObjAllocator,
+        globals: &'a mut Globals,
+        options: &'a VeloxOptions,
+        interrupted: &'a AtomicBool,
+        output: &'a mut dyn Write,
+        error_output: &'a mut dyn Write,
+        debugger: Option<&'a mut Box<dyn Debugger>>,
+        chunk: &'a Chunk,
+    ) -> Self {
         Self {
             stack,
             allocator,
+            globals,
+            options,
+            interrupted,
+            output,
+            error_output,
+            debugger,
             chunk,
             ip: chunk.code.iter(),
-            globals: HashMap::new(),
+            frame_base: 0,
+            max_stack_depth: 0,
+            instruction_count: 0,
+            instruction_start: 0,
+            underflow: false,
+            placeholder: Value::Nil,
         }
     }
 
+    // The frame's slots above the reserved one, which is all that tracing and debuggers show.
+    fn frame_values(&self) -> &[Value] {
+        return self.stack.get(self.frame_base + 1..).unwrap_or(&[]);
+    }
+
     fn instruction_offset(&self) -> usize {
         self.chunk.code.len() - self.ip.as_slice().len()
     }
 
     fn run(&mut self) -> InterpretResult {
+        self.frame_base = self.stack.len();
+        self.push(Value::Nil);
+
         loop {
-            if DEBUG_TRACE_EXECUTION {
-                print!("          ");
-                for value in self.stack.iter() {
-                    print!("[ ");
-                    value.print();
-                    print!(" ]");
+            // A well-formed chunk always ends in a return, but one built by hand may not.
+            if self.ip.as_slice().is_empty() {
+                self.runtime_error("Reached end of chunk without return.");
+                return InterpretResult::RuntimeError;
+            }
+
+            // Tracing goes to stderr so that stdout only ever carries the program's own output.
+            if self.options.trace_execution {
+                eprint!("          ");
+                for value in self.frame_values().iter() {
+                    eprint!("[ {} ]", value.stringify(self.allocator));
                 }
-                println!("");
+                eprintln!("");
 
-                self.chunk.dissassemble_instruction(self.instruction_offset());
+                self.chunk.dissassemble_instruction(self.instruction_offset(), self.allocator);
+            }
+
+            // Taken out for the call so that the context can borrow the rest of the runner.
+            if let Some(debugger) = self.debugger.take() {
+                debugger.before_instruction(&DebugContext {
+                    chunk: self.chunk,
+                    offset: self.instruction_offset(),
+                    stack: self.frame_values(),
+                    globals: self.globals,
+                    allocator: self.allocator,
+                });
+                self.debugger = Some(debugger);
+            }
+
+            if cfg!(debug_assertions) {
+                self.instruction_start = self.instruction_offset();
             }
 
             let instruction: u8 = self.read_byte();
-            let op: Op = unsafe { instruction.try_into().unwrap_unchecked() };
+            let op: Op = match instruction.try_into() {
+                Ok(op) => op,
+                Err(_) => {
+                    self.runtime_error(&format!("Unknown opcode {instruction}."));
+                    return InterpretResult::RuntimeError;
+                },
+            };
+
+            if self.ip.as_slice().len() < op.operand_bytes() {
+                self.runtime_error("Reached end of chunk without return.");
+                return InterpretResult::RuntimeError;
+            }
+
+            // Without a limit nothing is counted; the only cost is this one check.
+            if let Some(limit) = self.options.max_instructions {
+                self.instruction_count += 1;
+
+                if self.instruction_count > limit {
+                    self.runtime_error("Instruction limit exceeded.");
+                    return InterpretResult::RuntimeError;
+                }
+            }
+
+            if self.interrupted.load(Ordering::Relaxed) {
+                self.interrupted.store(false, Ordering::Relaxed);
+                self.runtime_error("Interrupted.");
+                return InterpretResult::RuntimeError;
+            }
+
             let result: Option<InterpretResult> = match op {
                 Op::Constant => {
                     let constant: Value = self.read_constant();
@@ -127,49 +577,115 @@ impl<'a> Runner<'a> {
                     self.pop();
                     None
                 },
+                Op::Swap => {
+                    let (top, below): (Value, Value) = (self.peek(0), self.peek(1));
+
+                    if !self.underflow {
+                        let length: usize = self.stack.len();
+                        self.stack[length - 2] = top;
+                        self.stack[length - 1] = below;
+                    }
+
+                    None
+                },
+                // A local's slot counts from the base of the running frame.
+                Op::GetLocal => {
+                    let slot: usize = self.read_byte() as usize;
+                    self.push(self.stack[self.frame_base + slot]);
+
+                    None
+                },
+                Op::SetLocal => {
+                    let slot: usize = self.read_byte() as usize;
+                    self.stack[self.frame_base + slot] = self.peek(0);
+
+                    None
+                },
                 Op::GetGlobal => {
                     let reference: ObjRef<String> = self.read_string();
+                    let hash: u32 = self.allocator.hash(reference);
+
+                    let value: Option<Value> = match self.globals.get(reference, hash) {
+                        Some(value) => Some(*value),
+                        None => {
+                            let name: &String = self.allocator.deref(reference);
+
+                            natives::find_native(name).map(Value::Native)
+                        },
+                    };
 
-                    match self.globals.get(&reference) {
-                        Some(&value) => {
+                    match value {
+                        Some(value) => {
                             self.push(value);
                             None
                         },
                         None => {
                             let name: &String = self.allocator.deref(reference);
+                            let message: String = format!("Undefined variable '{name}'.");
 
-                            self.runtime_error("Undefined variable '{name}'.")
+                            self.runtime_error(&message)
                         }
                     }
                 },
                 Op::DefineGlobal => {
                     let reference: ObjRef<String> = self.read_string();
+                    let hash: u32 = self.allocator.hash(reference);
                     let value: Value = self.pop();
 
-                    self.globals.insert(reference, value);
+                    if self.globals.is_frozen(reference, hash) {
+                        let name: &String = self.allocator.deref(reference);
+                        let message: String = format!("Cannot redefine frozen global '{name}'.");
 
-                    None
+                        self.runtime_error(&message)
+                    } else {
+                        self.globals.insert(reference, hash, value);
+
+                        None
+                    }
                 },
                 Op::SetGlobal => {
                     let reference: ObjRef<String> = self.read_string();
+                    let hash: u32 = self.allocator.hash(reference);
 
-                    if self.globals.contains_key(&reference) {
-                        let name: &String = self.allocator.deref(reference);
+                    let name: &String = self.allocator.deref(reference);
+
+                    // A native can be read like a global, so assigning to its name shadows it the
+                    // way `var` does.
+                    let defined: bool = self.globals.contains_key(reference, hash)
+                        || natives::find_native(name).is_some();
+
+                    if !defined {
+                        let message: String = format!("Undefined variable '{name}'.");
+
+                        self.runtime_error(&message)
+                    } else if self.globals.is_frozen(reference, hash) {
+                        let message: String = format!("Cannot assign to frozen global '{name}'.");
 
-                        self.runtime_error("Undefined variable '{name}'.")
+                        self.runtime_error(&message)
                     } else {
                         let value: Value = self.peek(0);
 
-                        self.globals.insert(reference, value);
+                        self.globals.insert(reference, hash, value);
 
                         None
                     }
                 },
+                // Nothing has properties until instances exist, so both ops only report the error.
+                Op::GetProperty => {
+                    self.read_string();
+
+                    self.runtime_error("Only instances have properties.")
+                },
+                Op::SetProperty => {
+                    self.read_string();
+
+                    self.runtime_error("Only instances have fields.")
+                },
                 Op::Equal => {
-                    let a: Value = self.pop();
                     let b: Value = self.pop();
+                    let a: &mut Value = self.top_mut();
 
-                    self.push(Value::Bool(a == b));
+                    *a = Value::Bool(a.equals(&b));
 
                     None
                 },
@@ -178,88 +694,407 @@ impl<'a> Runner<'a> {
                 Op::Add => {
                     let (b, a) = (self.peek(0), self.peek(1));
 
+                    match self.add(a, b) {
+                        Some(value) => {
+                            self.pop();
+                            *self.top_mut() = value;
+
+                            None
+                        },
+                        None => self.runtime_error("Operands must be two numbers or two strings."),
+                    }
+                },
+                Op::AddLocals => {
+                    let (left, right) = (self.read_byte() as usize, self.read_byte() as usize);
+                    let a: Value = self.stack[self.frame_base + left];
+                    let b: Value = self.stack[self.frame_base + right];
+
+                    match self.add(a, b) {
+                        Some(value) => {
+                            self.push(value);
+                            None
+                        },
+                        None => self.runtime_error("Operands must be two numbers or two strings."),
+                    }
+                },
+                Op::AddConstant => {
+                    let b: Value = self.read_constant();
+                    let a: Value = self.peek(0);
+
+                    match self.add(a, b) {
+                        Some(value) => {
+                            *self.top_mut() = value;
+                            None
+                        },
+                        None => self.runtime_error("Operands must be two numbers or two strings."),
+                    }
+                },
+                Op::Subtract => binary_op !(self, Number, -),
+                Op::Multiply => {
+                    let (b, a) = (self.peek(0), self.peek(1));
+
                     match (&a, &b) {
                         (Value::Number(a), Value::Number(b)) => {
-                            let value: f64 = a + b;
+                            let value: f64 = a * b;
 
                             self.pop();
-                            self.pop();
-
-                            self.push(Value::Number(value));
+                            *self.top_mut() = Value::Number(value);
 
                             None
                         },
-                        (Value::String(a), Value::String(b)) => {
-                            let a: &String = self.allocator.deref(*a);
-                            let b: &String = self.allocator.deref(*b);
+                        (Value::String(string), Value::Number(count)) |
+                        (Value::Number(count), Value::String(string)) => {
+                            let string: &String = self.allocator.deref(*string);
+                            let length: Option<usize> = string
+                                .len()
+                                .checked_mul(*count as usize)
+                                .filter(|length| *length <= STRING_LENGTH_MAX);
 
-                            let value: String = format!("{a}{b}");
+                            if *count < 0.0 || count.fract() != 0.0 {
+                                self.runtime_error("String repeat count must be a non-negative integer.")
+                            } else if length.is_none() {
+                                self.runtime_error("String repeat result is too long.")
+                            } else {
+                                let value: String = string.repeat(*count as usize);
 
-                            self.pop();
-                            self.pop();
+                                self.pop();
+                                self.pop();
 
-                            let reference: ObjRef<String> = self.allocator.intern(value);
-                            self.push(Value::String(reference));
+                                let reference: ObjRef<String> = self.allocator.intern(value);
+                                self.push(Value::String(reference));
 
-                            None
+                                None
+                            }
                         },
-                        _ => self.runtime_error("Operands must be numbers."),
+                        _ => self.runtime_error("Operands must be two numbers or a string and a number."),
                     }
                 },
-                Op::Subtract => binary_op !(self, Number, -),
-                Op::Multiply => binary_op!(self, Number, *),
                 Op::Divide => binary_op!(self, Number, /),
+                Op::Range | Op::RangeInclusive => match (self.peek(1), self.peek(0)) {
+                    (Value::Number(start), Value::Number(end)) => {
+                        let inclusive: bool = op == Op::RangeInclusive;
+                        let range: ObjRef<Range> =
+                            self.allocator.alloc(Range { start, end, inclusive });
+
+                        self.pop();
+                        *self.top_mut() = Value::Range(range);
+
+                        None
+                    },
+                    (start, end) => {
+                        let message: String = format!(
+                            "Range bounds must be numbers, got {} and {}.",
+                            start.type_name(),
+                            end.type_name(),
+                        );
+
+                        self.runtime_error(&message)
+                    },
+                },
+                Op::In => match (self.peek(1), self.peek(0)) {
+                    (value, Value::Range(reference)) => {
+                        // Only numbers can fall in a range; anything else is simply not in it.
+                        let contains: bool = match value {
+                            Value::Number(value) => self.allocator.deref(reference).contains(value),
+                            _ => false,
+                        };
+
+                        self.pop();
+                        *self.top_mut() = Value::Bool(contains);
+
+                        None
+                    },
+                    (_, container) => {
+                        let message: String = format!(
+                            "Right operand of 'in' must be a range, got {}.",
+                            container.type_name(),
+                        );
+
+                        self.runtime_error(&message)
+                    },
+                },
                 Op::Not => {
-                    let value: Value = self.pop();
+                    let value: &mut Value = self.top_mut();
 
-                    self.push(Value::Bool(value.is_falsy()));
+                    *value = Value::Bool(!value.is_truthy());
 
                     None
                 }
+                Op::Concat => {
+                    let count: usize = self.read_byte() as usize;
+                    let start: usize = self.stack.len() - count;
+
+                    let mut value: String = String::new();
+                    for part in self.stack[start..].iter() {
+                        match part {
+                            Value::String(reference) => {
+                                let string: &String = self.allocator.deref(*reference);
+                                value.push_str(string);
+                            },
+                            _ => value.push_str(&part.stringify(self.allocator)),
+                        }
+                    }
+
+                    self.stack.truncate(start);
+
+                    let reference: ObjRef<String> = self.allocator.intern(value);
+                    self.push(Value::String(reference));
+
+                    None
+                },
                 Op::Print => {
                     let value: Value = self.pop();
 
+                    self.print_value(&value);
+                    writeln!(self.output).unwrap();
+
+                    None
+
+                }
+                Op::Echo => {
+                    let value: Value = self.pop();
+
                     match value {
                         Value::String(reference) => {
                             let value: &String = self.allocator.deref(reference);
-                            println!("{value}");
-                        }
-                        _ => {
-                            value.print();
-                            println!("");
+                            write!(self.output, "\"{value}\"").unwrap();
                         }
+                        _ => self.print_value(&value),
                     }
 
-                    None
+                    if self.options.show_types {
+                        write!(self.output, " : {}", value.type_name()).unwrap();
+                    }
 
+                    writeln!(self.output).unwrap();
+
+                    None
                 }
                 Op::Negate => {
                     match self.peek(0) {
                         Value::Number(value) => {
-                            self.pop();
-
-                            self.push(Value::Number(-value));
+                            *self.top_mut() = Value::Number(-value);
 
                             None
                         },
-                        _ => self.runtime_error("Operand must be a number"),
+                        value => {
+                            let message: String =
+                                format!("Operand must be a number, got {}.", value.type_name());
+
+                            self.runtime_error(&message)
+                        },
+                    }
+                },
+                Op::Jump => {
+                    let distance: usize = self.read_short() as usize;
+                    self.jump_to(self.instruction_offset() + distance);
+
+                    None
+                },
+                Op::JumpIfFalse => {
+                    let distance: usize = self.read_short() as usize;
+                    let condition: Value = self.peek(0);
+
+                    if self.options.strict_conditions && !matches!(condition, Value::Bool(_)) {
+                        self.runtime_error("Condition must be a boolean.")
+                    } else {
+                        if !condition.is_truthy() {
+                            self.jump_to(self.instruction_offset() + distance);
+                        }
+
+                        None
+                    }
+                },
+                Op::Loop => {
+                    let distance: usize = self.read_short() as usize;
+                    self.jump_to(self.instruction_offset() - distance);
+
+                    None
+                },
+                // Pushes a cursor over the iterable: an index into a range, or a byte offset into
+                // a string.
+                Op::IterNew => match self.peek(0) {
+                    Value::Range(_) | Value::String(_) => {
+                        self.push(Value::Number(0.0));
+
+                        None
+                    },
+                    value => {
+                        let message: String = format!(
+                            "Can only iterate over ranges and strings, got {}.",
+                            value.type_name(),
+                        );
+
+                        self.runtime_error(&message)
+                    },
+                },
+                Op::IterNext => {
+                    let distance: usize = self.read_short() as usize;
+
+                    match (self.peek(1), self.peek(0)) {
+                        (iterable, Value::Number(cursor)) => {
+                            match self.iterate(iterable, cursor as usize) {
+                                Some((value, cursor)) => {
+                                    *self.top_mut() = Value::Number(cursor as f64);
+                                    self.push(value);
+                                },
+                                None => self.jump_to(self.instruction_offset() + distance),
+                            }
+                        },
+                        _ => unreachable!("iterator cursor is not a number"),
+                    }
+
+                    None
+                },
+                // Nothing is callable until function objects exist.
+                Op::Call => {
+                    let count: usize = self.read_byte() as usize;
+
+                    match self.peek(count) {
+                        Value::Native(native) => self.call_native(native, count),
+                        _ => self.runtime_error("Can only call functions and classes."),
+                    }
+                },
+                Op::Invoke => {
+                    let name: ObjRef<String> = self.read_string();
+                    let count: usize = self.read_byte() as usize;
+                    let receiver: Value = self.peek(count);
+                    let name: &String = self.allocator.deref(name);
+
+                    match methods::find_method(&receiver, name) {
+                        Some(method) => self.invoke(method, receiver, count),
+                        None => {
+                            let message: String =
+                                format!("Undefined method '{name}' on {}.", receiver.type_name());
+
+                            self.runtime_error(&message)
+                        },
                     }
                 },
                 Op::Return => {
+                    self.stack.truncate(self.frame_base);
+
                     Some(InterpretResult::Ok)
                 },
             };
 
+            if cfg!(debug_assertions) && self.underflow {
+                self.stack_underflow();
+                return InterpretResult::RuntimeError;
+            }
+
             if let Some(result) = result {
                 return result;
             }
+
+            if self.stack.len() > self.max_stack_depth {
+                self.max_stack_depth = self.stack.len();
+
+                if self.max_stack_depth > self.stack_limit() {
+                    self.runtime_error("Stack overflow.");
+                    return InterpretResult::RuntimeError;
+                }
+            }
+        }
+    }
+
+    // Shared by OP_ADD and the superinstructions that fuse it with their operands. None when the
+    // operands can't be added.
+    fn add(&mut self, a: Value, b: Value) -> Option<Value> {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => return Some(Value::Number(a + b)),
+            (Value::String(a), Value::String(b)) => {
+                let a: &String = self.allocator.deref(a);
+                let b: &String = self.allocator.deref(b);
+
+                let value: String = format!("{a}{b}");
+
+                return Some(Value::String(self.allocator.intern(value)));
+            },
+            _ => return None,
+        }
+    }
+
+    fn call_native(&mut self, native: &Native, count: usize) -> Option<InterpretResult> {
+        if native.arity.map_or(false, |arity| arity != count) {
+            let message: String =
+                format!("Expected {} arguments but got {}.", native.arity.unwrap(), count);
+            return self.runtime_error(&message);
+        }
+
+        let arguments: Vec<Value> = self.stack[(self.stack.len() - count)..].to_vec();
+
+        match (native.function)(self.allocator, self.options, self.output, &arguments) {
+            Ok(value) => {
+                self.stack.truncate(self.stack.len() - count);
+                *self.top_mut() = value;
+
+                return None;
+            },
+            Err(message) => return self.runtime_error(&message),
         }
     }
 
+    fn invoke(&mut self, method: &Method, receiver: Value, count: usize) -> Option<InterpretResult> {
+        if count != method.arity {
+            let message: String = format!("Expected {} arguments but got {}.", method.arity, count);
+            return self.runtime_error(&message);
+        }
+
+        let arguments: Vec<Value> = self.stack[(self.stack.len() - count)..].to_vec();
+
+        match (method.function)(self.allocator, receiver, &arguments) {
+            Ok(value) => {
+                self.stack.truncate(self.stack.len() - count);
+                *self.top_mut() = value;
+
+                return None;
+            },
+            Err(message) => return self.runtime_error(&message),
+        }
+    }
+
+    fn iterate(&mut self, iterable: Value, cursor: usize) -> Option<(Value, usize)> {
+        match iterable {
+            Value::Range(reference) => {
+                let range: &Range = self.allocator.deref(reference);
+
+                if cursor < range.len() {
+                    return Some((Value::Number(range.start + cursor as f64), cursor + 1));
+                }
+
+                return None;
+            },
+            // Strings iterate by Unicode scalar value, each as a one-character string.
+            Value::String(reference) => {
+                let character: char = self.allocator.deref(reference)[cursor..].chars().next()?;
+                let value: ObjRef<String> = self.allocator.intern(character.to_string());
+
+                return Some((Value::String(value), cursor + character.len_utf8()));
+            },
+            _ => unreachable!("cannot iterate over {}", iterable.type_name()),
+        }
+    }
+
+    fn jump_to(&mut self, offset: usize) -> () {
+        self.ip = self.chunk.code[offset..].iter();
+    }
+
+    fn print_value(&mut self, value: &Value) -> () {
+        write!(self.output, "{}", value.display(self.allocator, self.options)).unwrap();
+    }
+
     fn read_byte(&mut self) -> u8 {
         return unsafe { *self.ip.next().unwrap_unchecked() };
     }
 
+    fn read_short(&mut self) -> u16 {
+        let high: u8 = self.read_byte();
+        let low: u8 = self.read_byte();
+
+        return (high as u16) << 8 | low as u16;
+    }
+
     fn read_constant(&mut self) -> Value {
         return self.chunk.constants[self.read_byte() as usize].clone(); // TODO: Fix this when GC
     }
@@ -267,33 +1102,106 @@ impl<'a> Runner<'a> {
     fn read_string(&mut self) -> ObjRef<String> {
         match self.read_constant() {
             Value::String(reference) => reference,
-            None => { panic!("Constant is not String!") },
+            _ => panic!("Constant is not String!"),
         }
     }
 
     fn runtime_error(&mut self, message: &str) -> Option<InterpretResult> {
-        eprintln!("{message}");
+        // An instruction that underflowed worked on placeholders, so its own error is noise.
+        if cfg!(debug_assertions) && self.underflow {
+            return self.stack_underflow();
+        }
 
-        let instruction: usize = self.instruction_offset() - 1;
-        let line: usize = self.chunk.lines[instruction];
+        writeln!(self.error_output, "{message}").unwrap();
 
-        eprintln!("[line {line}] in script");
+        // An empty chunk has no instruction, and so no line, to blame.
+        let instruction: usize = self.instruction_offset().saturating_sub(1);
+        let line: usize = self.chunk.lines.get(instruction).copied().unwrap_or(0);
+
+        let location: String = source_location(self.chunk.source_name.as_deref(), line);
+        writeln!(self.error_output, "{location} in script").unwrap();
 
         self.stack.clear();
 
         return Some(InterpretResult::RuntimeError);
     }
 
-    fn peek(&self, distance: usize) -> Value {
+    fn peek(&mut self, distance: usize) -> Value {
+        if cfg!(debug_assertions) && distance >= self.frame_values().len() {
+            self.underflow = true;
+            return Value::Nil;
+        }
+
         let index: usize = self.stack.len() - 1 - distance;
         return self.stack[index];
     }
 
     fn push(&mut self, value: Value) -> () {
+        debug_assert!(
+            match value {
+                Value::String(reference) => self.allocator.is_interned(reference),
+                _ => true,
+            },
+            "string values must be interned so that equality can compare references",
+        );
+
+        // The stack is only ever addressed by index, so letting the Vec reallocate is safe.
+        if self.stack.len() == self.stack.capacity() {
+            let capacity: usize = (self.stack.capacity() * 2)
+                .min(self.stack_limit())
+                .max(self.stack.len() + 1);
+            self.stack.reserve_exact(capacity - self.stack.len());
+        }
+
         self.stack.push(value);
     }
 
+    // The reserved slot zero doesn't count against the configured size.
+    fn stack_limit(&self) -> usize {
+        return self.options.max_stack_size.unwrap_or(self.options.stack_size) + 1;
+    }
+
     fn pop(&mut self) -> Value {
+        if cfg!(debug_assertions) && self.frame_values().is_empty() {
+            self.underflow = true;
+            return Value::Nil;
+        }
+
         return self.stack.pop().expect("Empty stack");
     }
+
+    // Debug builds catch underflow from a compiler bug or a hand-built chunk and report the
+    // instruction and the stack; release builds keep the unchecked fast path and panic.
+    fn stack_underflow(&mut self) -> Option<InterpretResult> {
+        self.underflow = false;
+
+        let mut instruction: String = String::new();
+        self.chunk
+            .write_instruction(self.instruction_start, self.allocator, &mut instruction)
+            .unwrap();
+
+        let stack: String = match self.frame_values().is_empty() {
+            true => "(empty)".to_owned(),
+            false => self.frame_values()
+                .iter()
+                .map(|value| format!("[ {} ]", value.stringify(self.allocator)))
+                .collect(),
+        };
+
+        let message: String =
+            format!("Stack underflow.\n  at {}\n  stack: {stack}", instruction.trim_end());
+
+        return self.runtime_error(&message);
+    }
+
+    // Unary and arithmetic ops overwrite their left operand instead of popping and pushing it.
+    fn top_mut(&mut self) -> &mut Value {
+        if cfg!(debug_assertions) && self.frame_values().is_empty() {
+            self.underflow = true;
+            self.placeholder = Value::Nil;
+            return &mut self.placeholder;
+        }
+
+        return self.stack.last_mut().expect("Empty stack");
+    }
 }
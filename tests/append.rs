@@ -0,0 +1,96 @@
+extern crate rvelox;
+
+use rvelox::{
+    chunk::{AppendError, Chunk, Op},
+    compiler::Compiler,
+    object::ObjAllocator,
+    value::Value,
+    vm::{InterpretResult, VM},
+};
+
+fn compile(source: &str, allocator: &mut ObjAllocator) -> Chunk {
+    let mut chunk: Chunk = Chunk::new();
+    assert!(Compiler::new(source, allocator, &mut chunk, false).compile());
+
+    return chunk;
+}
+
+#[test]
+fn appended_chunk_runs_after_the_host() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let mut host: Chunk = compile("var a = 1;\nvar b = 2;", &mut allocator);
+    let other: Chunk = compile("var c = 3;\nfor (i in 0..2) print b;", &mut allocator);
+
+    let host_length: usize = host.code.len();
+    host.append(&other, &mut allocator).unwrap();
+
+    assert_eq!(host.verify(), Ok(()));
+    assert_eq!(host.code.len(), host_length - 1 + other.code.len());
+    // The trailing return is gone and the other chunk's lines come along unchanged.
+    assert_eq!(&host.lines[host_length - 1..], &other.lines[..]);
+
+    let mut assembly: String = String::new();
+    host.write_chunk("linked", &allocator, &mut assembly).unwrap();
+
+    // Constants shared by both chunks are stored once.
+    assert_eq!(host.constants.len(), 7, "{}", assembly);
+    assert!(assembly.contains("0014    | OP_CONSTANT         3 '2'\n"), "{}", assembly);
+    assert!(assembly.contains("0021    | OP_GET_GLOBAL       2 'b'\n"), "{}", assembly);
+    assert!(assembly.contains("0018    | OP_ITER_NEXT       18 -> 28\n"), "{}", assembly);
+    assert!(assembly.contains("0025    | OP_LOOP            25 -> 18\n"), "{}", assembly);
+}
+
+#[test]
+fn appended_code_is_reached() {
+    let mut host: Chunk = Chunk::new();
+    host.emit(Op::Nil, 1);
+    host.emit(Op::Pop, 1);
+    host.emit(Op::Return, 1);
+
+    let mut other: Chunk = Chunk::new();
+    other.add_constant(Value::Bool(true));
+    other.write(Op::Constant.into(), 2);
+    other.write(0, 2);
+    other.emit(Op::Negate, 2);
+    other.emit(Op::Return, 2);
+
+    assert_eq!(VM::new().run_chunk(&host), InterpretResult::Ok);
+
+    host.append(&other, &mut ObjAllocator::new()).unwrap();
+
+    // Negating a bool fails, which shows that the other chunk's code ran.
+    assert_eq!(VM::new().run_chunk(&host), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn constant_pool_overflow_is_an_error() {
+    let mut host: Chunk = Chunk::new();
+    for number in 0..256 {
+        host.add_constant(Value::Number(number as f64));
+    }
+    host.emit(Op::Return, 1);
+
+    let mut other: Chunk = Chunk::new();
+    other.add_constant(Value::Number(0.0));
+    other.add_constant(Value::Number(256.0));
+    other.write(Op::Constant.into(), 1);
+    other.write(1, 1);
+    other.emit(Op::Return, 1);
+
+    let result: Result<(), AppendError> = host.append(&other, &mut ObjAllocator::new());
+
+    assert_eq!(result, Err(AppendError::TooManyConstants));
+    assert_eq!(host.constants.len(), 256);
+    assert_eq!(host.code, vec![Op::Return.into()]);
+}
+
+#[test]
+fn invalid_chunks_are_rejected() {
+    let mut other: Chunk = Chunk::new();
+    other.emit(Op::Nil, 1);
+
+    assert!(matches!(
+        Chunk::new().append(&other, &mut ObjAllocator::new()),
+        Err(AppendError::Invalid(_))
+    ));
+}
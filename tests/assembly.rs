@@ -0,0 +1,40 @@
+extern crate rvelox;
+
+use rvelox::compiler::Severity;
+
+#[test]
+fn string_constants_render_their_contents() {
+    let assembly: String = rvelox::compile_to_assembly("var greeting = \"hello\";").unwrap();
+
+    assert!(assembly.contains("OP_CONSTANT         1 'hello'\n"), "{}", assembly);
+    assert!(assembly.contains("0000 string           'greeting'\n"), "{}", assembly);
+}
+
+#[test]
+fn compile_errors_are_returned() {
+    let diagnostics = rvelox::compile_to_assembly("print 1 +;").unwrap_err();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].message, "Expect expression.");
+}
+
+#[test]
+fn number_constants_use_thousands_separators() {
+    let assembly: String =
+        rvelox::compile_to_assembly("print 1000000; print -1234.5; print 999;").unwrap();
+
+    assert!(assembly.contains("OP_CONSTANT         0 '1,000,000'\n"), "{}", assembly);
+    assert!(assembly.contains("0000 number           '1,000,000'\n"), "{}", assembly);
+    assert!(assembly.contains("0001 number           '1,234.5'\n"), "{}", assembly);
+    assert!(assembly.contains("0002 number           '999'\n"), "{}", assembly);
+}
+
+#[test]
+fn method_calls_compile_to_a_single_invoke() {
+    let assembly: String = rvelox::compile_to_assembly("var s = \"abc\";\nprint s.len();").unwrap();
+
+    assert!(assembly.contains("OP_INVOKE        (0 args)    2 'len'\n"), "{}", assembly);
+    assert!(!assembly.contains("OP_GET_PROPERTY"), "{}", assembly);
+    assert!(!assembly.contains("OP_CALL"), "{}", assembly);
+}
@@ -0,0 +1,159 @@
+extern crate rvelox;
+
+mod common;
+
+use std::{
+    process::Output,
+    time::{Duration, Instant},
+};
+
+use common::{run_rvelox, TempScript};
+use rvelox::vm::{InterpretResult, VM};
+
+// Benchmarks are ignored by default; run them with `cargo test --release -- --ignored --nocapture`.
+fn run_benchmark(name: &str, source: &str, flags: &[&str]) -> Duration {
+    let script: TempScript = TempScript::new(&format!("rvelox-bench-{name}.vlx"), source);
+    let args: Vec<&str> = [flags, &[&script.path]].concat();
+
+    let start: Instant = Instant::now();
+    let output: Output = run_rvelox(&args, &[], "");
+    let elapsed: Duration = start.elapsed();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    eprintln!("{name}: {elapsed:?}");
+
+    return elapsed;
+}
+
+// The language has no loops yet, so the "loop" is unrolled into straight-line code.
+#[test]
+#[ignore]
+fn negate_loop() {
+    let mut source: String = String::from("var x = 1;\nvar y = true;\n");
+    for _ in 0..100_000 {
+        source.push_str("x = -(-(-x)) * -1;\ny = !!y == !(x < 0);\n");
+    }
+    source.push_str("print x;\n");
+
+    run_benchmark("negate_loop", &source, &[]);
+}
+
+#[test]
+#[ignore]
+fn comparison_loop() {
+    let mut source: String = String::from("var x = 1;\nvar y = 2;\nvar z = false;\n");
+    for _ in 0..100_000 {
+        source.push_str("z = (x < y) == !(x > y) == (y == x);\n");
+    }
+    source.push_str("print z;\n");
+
+    run_benchmark("comparison_loop", &source, &[]);
+}
+
+#[test]
+#[ignore]
+fn interpolation_loop() {
+    let mut source: String = String::from("var name = \"world\";\nvar count = 3;\nvar s = nil;\n");
+    for _ in 0..50_000 {
+        source.push_str("s = \"hello ${name}, ${count} and ${count + 1} for ${name}!\";\n");
+    }
+    source.push_str("print s;\n");
+
+    run_benchmark("interpolation_loop", &source, &[]);
+}
+
+// Method calls compile to a single OP_INVOKE, which looks the method up without creating a
+// bound method object first.
+#[test]
+#[ignore]
+fn method_call_loop() {
+    let source: &str = concat!(
+        "var s = \"abc\";\nvar b = buffer();\nvar n = 0;\n",
+        "for (i in 0..100000) n = n + s.len() + b.append(i).len() - s.upper().len();\n",
+        "print n;\n",
+    );
+
+    run_benchmark("method_call_loop", source, &[]);
+}
+
+// Nearly every instruction in the inner loop is an addition that `--opt` fuses with its operands.
+#[test]
+#[ignore]
+fn local_add_loop() {
+    let source: &str = concat!(
+        "var n = 0;\n",
+        "for (i in 0..3000) for (j in 0..1000) n = i + j + 1 + i + j + 2;\n",
+        "print n;\n",
+    );
+
+    // The best of a few runs, since the difference is small next to the noise of one run.
+    let best = |name: &str, flags: &[&str]| -> Duration {
+        return (0..5).map(|_| run_benchmark(name, source, flags)).min().unwrap();
+    };
+
+    let plain: Duration = best("local_add_loop", &[]);
+    let optimized: Duration = best("local_add_loop_opt", &["--opt"]);
+
+    eprintln!("speedup: {:.1}%", (1.0 - optimized.as_secs_f64() / plain.as_secs_f64()) * 100.0);
+}
+
+#[test]
+#[ignore]
+fn global_access_loop() {
+    let source: &str = concat!(
+        "var a = 0;\nvar b = 1;\nvar c = 2;\nvar d = 3;\n",
+        "for (i in 0..1000000) a = a + b + c - d;\n",
+        "print a;\n",
+    );
+
+    run_benchmark("global_access_loop", source, &[]);
+}
+
+// Every concatenation produces a string that is already interned after the first iteration,
+// and every interpolation one that is not.
+#[test]
+#[ignore]
+fn interning_loop() {
+    let source: &str = concat!(
+        "var x = \"ab\";\nvar y = \"cd\";\nvar s = nil;\n",
+        "for (i in 0..300000) s = x + y + x + y;\n",
+        "for (i in 0..100000) s = \"key ${i}\";\n",
+        "print s;\n",
+    );
+
+    run_benchmark("interning_loop", source, &[]);
+}
+
+// Runs in-process so that the allocator can report how much memory the script left behind.
+fn run_in_process(name: &str, source: &str) -> (Duration, usize) {
+    let mut vm: VM = VM::new();
+
+    let start: Instant = Instant::now();
+    let result: InterpretResult = vm.interpret(source);
+    let elapsed: Duration = start.elapsed();
+
+    assert_eq!(result, InterpretResult::Ok);
+    eprintln!("{name}: {elapsed:?}, {} bytes allocated", vm.allocated_bytes());
+
+    return (elapsed, vm.allocated_bytes());
+}
+
+#[test]
+#[ignore]
+fn string_building_loop() {
+    let (concat_time, concat_bytes) = run_in_process(
+        "concat_loop",
+        "var s = \"\";\nfor (i in 0..10000) s = s + \"ab\";\nprint s.len() == 20000;\n",
+    );
+    let (buffer_time, buffer_bytes) = run_in_process(
+        "buffer_loop",
+        concat!(
+            "var b = buffer();\nfor (i in 0..10000) b.append(\"ab\");\n",
+            "print b.to_string().len() == 20000;\n",
+        ),
+    );
+
+    // Every concatenation interns a new, longer string, so that loop allocates quadratically.
+    assert!(buffer_bytes * 10 < concat_bytes);
+    assert!(buffer_time < concat_time);
+}
@@ -0,0 +1,31 @@
+extern crate rvelox;
+
+use rvelox::{
+    velox::VeloxOptions,
+    vm::{InterpretResult, VM},
+};
+
+#[test]
+fn capacity_hints_keep_globals_resolvable() {
+    let mut vm: VM = VM::with_options(VeloxOptions {
+        globals_capacity: Some(4096),
+        strings_capacity: Some(4096),
+        ..VeloxOptions::default()
+    });
+
+    // Each global gets its own chunk, which keeps every chunk under the constant limit.
+    for index in 0..2000 {
+        assert_eq!(vm.interpret(&format!("var global{index} = {index};")), InterpretResult::Ok);
+    }
+    assert_eq!(
+        vm.interpret("var total = global0 + global999 + global1999;"),
+        InterpretResult::Ok,
+    );
+
+    let total: Option<String> = vm
+        .globals_iter()
+        .find(|(name, _)| *name == "total")
+        .map(|(_, value)| vm.stringify(&value));
+
+    assert_eq!(total.as_deref(), Some("2998"));
+}
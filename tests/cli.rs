@@ -0,0 +1,170 @@
+mod common;
+
+use std::process::Output;
+
+use common::{run_rvelox, TempScript};
+
+#[test]
+fn opt_fuses_additions_into_superinstructions() {
+    let script: TempScript = TempScript::new(
+        "rvelox-superinstructions.vlx",
+        "for (a in 0..1) for (b in 0..1) print a + b + 1;\n",
+    );
+
+    let dump = |flags: &[&str]| -> String {
+        let args: Vec<&str> = [flags, &["--dump-code", &script.path]].concat();
+        let output: Output = run_rvelox(&args, &[], "");
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        return String::from_utf8_lossy(&output.stderr).into_owned();
+    };
+
+    let plain: String = dump(&[]);
+    let optimized: String = dump(&["--opt"]);
+
+    assert!(!plain.contains("OP_ADD_"), "{}", plain);
+    assert!(optimized.contains("OP_ADD_LOCALS       3    6\n"), "{}", optimized);
+    assert!(optimized.contains("OP_ADD_CONSTANT     1 '1'\n"), "{}", optimized);
+    assert!(!optimized.contains("OP_ADD\n"), "{}", optimized);
+}
+
+#[test]
+fn trace_is_written_to_stderr() {
+    let output: Output = run_rvelox(&["--trace"], &[], "print 1 + 2;\n");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert!(stderr.contains("OP_ADD"), "expected a trace on stderr, got {:?}", stderr);
+    assert!(stderr.contains("[ 1 ][ 2 ]"), "expected the stack in the trace, got {:?}", stderr);
+}
+
+#[test]
+fn debugger_follows_scripted_commands() {
+    let script: TempScript =
+        TempScript::new("rvelox-debugger.vlx", "var x = 1;\nx = x + 2;\nprint x;\n");
+
+    let output: Output =
+        run_rvelox(&["--debug", &script.path], &[], "s\nb 2\nc\np x\nstack\nc\n");
+
+    let transcript: Vec<String> = String::from_utf8_lossy(&output.stderr)
+        .split("(debug) ")
+        .map(|chunk| chunk.trim_end().to_owned())
+        .collect();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert!(transcript[1].starts_with("0002    | OP_DEFINE_GLOBAL"), "{:?}", transcript);
+    assert_eq!(transcript[2], "Breakpoint set at line 2 (offset 0004).");
+    assert!(transcript[3].starts_with("0004    2 OP_GET_GLOBAL"), "{:?}", transcript);
+    assert_eq!(transcript[4], "x = 1");
+    assert_eq!(transcript[5], "");
+    assert_eq!(transcript.len(), 7);
+}
+
+#[test]
+fn compilation_stops_after_twenty_errors() {
+    let errors: String = "print ;\n".repeat(50);
+
+    // Inside a block the errors start a line later.
+    for (source, first_line) in [(errors.clone(), 1), (format!("{{\n{errors}}}\n"), 2)] {
+        let script: TempScript =
+            TempScript::new(&format!("rvelox-max-errors-{first_line}.vlx"), &source);
+
+        let output: Output = run_rvelox(&[&script.path], &[], "");
+
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        let lines: Vec<&str> = stderr.lines().collect();
+        let (last_error, abort) = (first_line + 19, first_line + 20);
+
+        assert_eq!(output.status.code(), Some(65));
+        assert_eq!(lines.len(), 21, "{}", stderr);
+        assert_eq!(
+            lines[19],
+            format!("[{} line {last_error}] Error at ';': Expect expression.", script.path),
+        );
+        assert_eq!(
+            lines[20],
+            format!("[{} line {abort}] Error at 'print': Too many errors; aborting.", script.path),
+        );
+    }
+}
+
+#[test]
+fn time_reports_compile_and_run_durations() {
+    let script: TempScript = TempScript::new("rvelox-time.vlx", "print 1 + 2;\n");
+
+    let output: Output = run_rvelox(&["--time", &script.path], &[], "");
+
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    let lines: Vec<&str> = stderr.lines().collect();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert_eq!(lines.len(), 2, "{:?}", stderr);
+    assert!(lines[0].starts_with("compile time: "), "{:?}", stderr);
+    assert!(lines[1].starts_with("run time: "), "{:?}", stderr);
+}
+
+#[test]
+fn print_parses_as_statement_or_call_depending_on_flag() {
+    let script: TempScript =
+        TempScript::new("rvelox-print-as-function.vlx", "print (1);\nprint (1, 2);\n");
+
+    let run = |flags: &[&str]| -> String {
+        let args: Vec<&str> = [flags, &[&script.path]].concat();
+        let output: Output = run_rvelox(&args, &[], "");
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        return String::from_utf8_lossy(&output.stdout).into_owned();
+    };
+
+    // As a statement the parentheses group a comma expression; as a call they hold arguments.
+    assert_eq!(run(&[]), "1\n2\n");
+    assert_eq!(run(&["--print-as-function"]), "1\n1 2\n");
+}
+
+#[test]
+fn environment_variables_set_options() {
+    let script: TempScript = TempScript::new("rvelox-env-stack-size.vlx", "print 1 + 2;\n");
+    let output: Output = run_rvelox(&[&script.path], &[("RVELOX_STACK_SIZE", "1")], "");
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("Stack overflow.\n[{} line 1] in script\n", script.path),
+    );
+
+    let script: TempScript = TempScript::new("rvelox-env-dump-code.vlx", "print 1;\n");
+    let output: Output = run_rvelox(&[&script.path], &[("RVELOX_DUMP_CODE", "1")], "");
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(stderr.starts_with("== code ==\n"), "{:?}", stderr);
+    assert!(stderr.contains("OP_PRINT"), "{:?}", stderr);
+}
+
+#[test]
+fn flags_override_environment_variables() {
+    let script: TempScript = TempScript::new("rvelox-env-override.vlx", "print 1 + 2;\n");
+    let output: Output =
+        run_rvelox(&["--stack-size=1", &script.path], &[("RVELOX_STACK_SIZE", "2")], "");
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("Stack overflow.\n[{} line 1] in script\n", script.path),
+    );
+}
+
+#[test]
+fn malformed_environment_variables_keep_the_default() {
+    let script: TempScript = TempScript::new("rvelox-env-malformed.vlx", "print 1 + 2;\n");
+    let output: Output = run_rvelox(
+        &[&script.path],
+        &[("RVELOX_STACK_SIZE", "abc"), ("RVELOX_TRACE", "yes")],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Ignoring invalid RVELOX_TRACE \"yes\".\nIgnoring invalid RVELOX_STACK_SIZE \"abc\".\n",
+    );
+}
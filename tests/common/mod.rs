@@ -0,0 +1,64 @@
+// Each test crate uses only some of these helpers.
+#![allow(dead_code)]
+
+use std::{
+    env,
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Child, Command, Output, Stdio},
+};
+
+// A small xorshift generator, so property tests need no dependencies and are reproducible.
+pub struct Random(pub u64);
+
+impl Random {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0;
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        return self.next() % bound;
+    }
+}
+
+// A script in the temporary directory, removed again when dropped so that a failed assertion
+// does not leave it behind.
+pub struct TempScript {
+    pub path: String,
+}
+
+impl TempScript {
+    pub fn new(name: &str, source: &str) -> TempScript {
+        let path: PathBuf = env::temp_dir().join(name);
+        fs::write(&path, source).unwrap();
+
+        return TempScript { path: path.to_str().unwrap().to_owned() };
+    }
+}
+
+impl Drop for TempScript {
+    fn drop(&mut self) -> () {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Runs the binary with `input` piped to stdin, so that without a path it reads a REPL session.
+// The variables in `vars` are set for this run only.
+pub fn run_rvelox(args: &[&str], vars: &[(&str, &str)], input: &str) -> Output {
+    let mut child: Child = Command::new(env!("CARGO_BIN_EXE_rvelox"))
+        .args(args)
+        .envs(vars.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+
+    return child.wait_with_output().unwrap();
+}
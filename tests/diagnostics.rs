@@ -0,0 +1,68 @@
+extern crate rvelox;
+
+mod common;
+
+use std::process::Output;
+
+use common::{run_rvelox, TempScript};
+use rvelox::{
+    compiler::{Diagnostic, Severity},
+    velox::VeloxOptions,
+    vm::{InterpretResult, VM},
+};
+
+const SOURCE: &str = "print 1;\nprint 1 +;\nreturn;\nvar a = 1;\na == a;\n";
+
+// Compile errors are collected rather than printed, so a host gets them without touching stderr.
+#[test]
+fn compile_errors_are_returned_by_check() {
+    let diagnostics: Vec<Diagnostic> = VM::new().check(SOURCE);
+    let rendered: Vec<String> = diagnostics.iter().map(Diagnostic::render).collect();
+
+    assert_eq!(
+        rendered,
+        vec![
+            "[line 2] Error at ';': Expect expression.",
+            "[line 3] Error at 'return': Can't return from top-level code.",
+            "[line 5] Warning at '==': Comparison of a value with itself.",
+        ],
+    );
+    assert_eq!(diagnostics[2].severity, Severity::Warning);
+}
+
+#[test]
+fn zero_max_errors_sets_no_limit() {
+    let mut vm: VM = VM::with_options(VeloxOptions { max_errors: 0, ..VeloxOptions::default() });
+
+    assert_eq!(vm.interpret("var clean = 1;"), InterpretResult::Ok);
+    assert_eq!(vm.check(&"print ;\n".repeat(50)).len(), 50);
+}
+
+#[test]
+fn binary_prints_the_rendered_diagnostics() {
+    let script: TempScript = TempScript::new("rvelox-diagnostics.vlx", SOURCE);
+    let output: Output = run_rvelox(&[&script.path], &[], "");
+
+    let expected: String = VM::new()
+        .check_named(SOURCE, &script.path)
+        .iter()
+        .map(|diagnostic| diagnostic.render() + "\n")
+        .collect();
+
+    assert_eq!(String::from_utf8_lossy(&output.stderr), expected);
+    assert_eq!(output.status.code(), Some(65));
+}
+
+// A global defined by an earlier run, such as a previous REPL line, counts as existing.
+#[test]
+fn redefining_a_global_from_an_earlier_run_warns() {
+    let mut vm: VM = VM::new();
+
+    assert_eq!(vm.interpret("var a = 1;"), InterpretResult::Ok);
+
+    let rendered: Vec<String> = vm.check("var a = 2;").iter().map(Diagnostic::render).collect();
+    assert_eq!(
+        rendered,
+        vec!["[line 1] Warning at 'a': Redefinition of an existing global variable."],
+    );
+}
@@ -0,0 +1,69 @@
+extern crate rvelox;
+
+use rvelox::{
+    chunk::{Chunk, Op},
+    natives,
+    object::ObjAllocator,
+    value::Value,
+    velox::VeloxOptions,
+    vm::{InterpretResult, VM},
+};
+
+#[test]
+fn values_debug_without_an_allocator() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let string: Value = Value::String(allocator.intern("hello".to_owned()));
+
+    assert_eq!(format!("{:?}", Value::Number(1.5)), "Number(1.5)");
+    assert_eq!(format!("{:?}", Value::Nil), "Nil");
+    assert_eq!(format!("{:?}", string), "String(ObjRef(0))");
+    assert_eq!(
+        format!("{:?}", Value::Native(natives::find_native("print").unwrap())),
+        "Native(<native fn print>)",
+    );
+}
+
+#[test]
+fn formatter_shows_string_contents() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let string: Value = Value::String(allocator.intern("hello".to_owned()));
+
+    assert_eq!(string.formatter(&allocator).to_string(), "hello");
+    assert_eq!(format!("{:?}", string.formatter(&allocator)), "String(ObjRef(0), \"hello\")");
+    assert_eq!(Value::Bool(true).formatter(&allocator).to_string(), "true");
+    assert_eq!(format!("{:?}", Value::Bool(true).formatter(&allocator)), "Bool(true)");
+}
+
+#[test]
+fn interpret_results_compare_directly() {
+    assert_eq!(VM::new().interpret("print 1;"), InterpretResult::Ok);
+    assert_eq!(VM::new().interpret("print 1 +;"), InterpretResult::CompileError);
+    assert_eq!(VM::new().interpret("print -nil;"), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn cloned_chunks_are_independent() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.emit(Op::Nil, 1);
+
+    let snapshot: Chunk = chunk.clone();
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(snapshot.code, vec![Op::Nil.into()]);
+    assert_eq!(format!("{:?}", Op::Return), "Return");
+    assert!(format!("{:?}", snapshot).starts_with("Chunk { code: [1], constants: [], lines: [1]"));
+}
+
+#[test]
+fn display_renders_nil_with_the_configured_text() {
+    let allocator: ObjAllocator = ObjAllocator::new();
+    let mut options: VeloxOptions = VeloxOptions::default();
+
+    assert_eq!(Value::Nil.display(&allocator, &options), "nil");
+
+    options.nil_text = String::new();
+    assert_eq!(Value::Nil.display(&allocator, &options), "");
+    assert_eq!(Value::Bool(false).display(&allocator, &options), "false");
+    // Only printing is affected: converting nil to a string is unchanged.
+    assert_eq!(Value::Nil.stringify(&allocator), "nil");
+}
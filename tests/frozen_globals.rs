@@ -0,0 +1,43 @@
+extern crate rvelox;
+
+use rvelox::{
+    value::Value,
+    vm::{InterpretResult, VM},
+};
+
+fn global(vm: &VM, name: &str) -> Option<String> {
+    return vm
+        .globals_iter()
+        .find(|(global, _)| *global == name)
+        .map(|(_, value)| vm.stringify(&value));
+}
+
+#[test]
+fn frozen_globals_can_be_read() {
+    let mut vm: VM = VM::new();
+    let mode: Value = vm.string_value("fast");
+    vm.define_frozen_global("limit", Value::Number(10.0));
+    vm.define_frozen_global("mode", mode);
+
+    assert_eq!(vm.interpret("var doubled = limit * 2; var copy = mode;"), InterpretResult::Ok);
+    assert_eq!(global(&vm, "doubled").as_deref(), Some("20"));
+    assert_eq!(global(&vm, "copy").as_deref(), Some("fast"));
+}
+
+#[test]
+fn assigning_a_frozen_global_is_a_runtime_error() {
+    let mut vm: VM = VM::new();
+    vm.define_frozen_global("limit", Value::Number(10.0));
+
+    assert_eq!(vm.interpret("limit = 11;"), InterpretResult::RuntimeError);
+    assert_eq!(global(&vm, "limit").as_deref(), Some("10"));
+}
+
+#[test]
+fn redefining_a_frozen_global_is_a_runtime_error() {
+    let mut vm: VM = VM::new();
+    vm.define_frozen_global("limit", Value::Number(10.0));
+
+    assert_eq!(vm.interpret("var limit = 11;"), InterpretResult::RuntimeError);
+    assert_eq!(global(&vm, "limit").as_deref(), Some("10"));
+}
@@ -0,0 +1,19 @@
+extern crate rvelox;
+
+use rvelox::vm::{InterpretResult, VM};
+
+#[test]
+fn globals_are_listed_in_definition_order() {
+    let mut vm: VM = VM::new();
+    let source: &str = "var zebra = 1;\nvar apple = \"two\";\nvar mango = nil;\nzebra = 3;\n";
+
+    assert_eq!(vm.interpret(source), InterpretResult::Ok);
+
+    // Assigning zebra again keeps its place.
+    let globals: Vec<(&str, String)> =
+        vm.globals_iter().map(|(name, value)| (name, vm.stringify(&value))).collect();
+    assert_eq!(
+        globals,
+        vec![("zebra", "3".to_owned()), ("apple", "two".to_owned()), ("mango", "nil".to_owned())],
+    );
+}
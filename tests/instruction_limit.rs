@@ -0,0 +1,29 @@
+extern crate rvelox;
+
+use rvelox::{
+    velox::VeloxOptions,
+    vm::{InterpretResult, VM},
+};
+
+fn limited(max_instructions: usize) -> VM {
+    return VM::with_options(VeloxOptions {
+        max_instructions: Some(max_instructions),
+        ..VeloxOptions::default()
+    });
+}
+
+#[test]
+fn infinite_loop_stops_at_the_limit() {
+    assert_eq!(limited(1000).interpret("do {} while (true);"), InterpretResult::RuntimeError);
+}
+
+// Each run gets the whole budget; instructions are not counted across runs.
+#[test]
+fn limit_applies_to_each_run() {
+    let mut vm: VM = limited(3);
+
+    // Two instructions, plus the closing return.
+    assert_eq!(vm.interpret("var a = 1;"), InterpretResult::Ok);
+    assert_eq!(vm.interpret("var b = 2;"), InterpretResult::Ok);
+    assert_eq!(vm.interpret("var c = a + b;"), InterpretResult::RuntimeError);
+}
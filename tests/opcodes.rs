@@ -0,0 +1,25 @@
+extern crate rvelox;
+
+use std::convert::TryFrom;
+
+use rvelox::chunk::Op;
+
+#[test]
+fn operand_bytes_follow_the_instruction_format() {
+    assert_eq!(Op::Constant.operand_bytes(), 1);
+    assert_eq!(Op::GetLocal.operand_bytes(), 1);
+    assert_eq!(Op::Return.operand_bytes(), 0);
+    assert_eq!(Op::Jump.operand_bytes(), 2);
+    assert_eq!(Op::Invoke.operand_bytes(), 2);
+}
+
+// Every byte that decodes to an opcode is visited exactly once, in byte order.
+#[test]
+fn iter_covers_every_opcode() {
+    let decoded: Vec<Op> = (0..=u8::MAX).filter_map(|byte| Op::try_from(byte).ok()).collect();
+    let iterated: Vec<Op> = Op::iter().copied().collect();
+
+    assert_eq!(iterated, decoded);
+    assert_eq!(iterated.first(), Some(&Op::Constant));
+    assert_eq!(iterated.last(), Some(&Op::Return));
+}
@@ -0,0 +1,61 @@
+extern crate rvelox;
+
+use rvelox::{
+    compiler::Parser,
+    scanner::{Scanner, TokenType},
+};
+
+fn messages(parser: &Parser) -> Vec<String> {
+    return parser.diagnostics.iter().map(|diagnostic| diagnostic.render()).collect();
+}
+
+#[test]
+fn peek_next_looks_past_current() {
+    let mut parser: Parser = Parser::new(Scanner::new("var a = 1;"));
+    parser.advance();
+
+    assert_eq!(parser.current.lexeme, "var");
+    assert_eq!(parser.peek_next().lexeme, "a");
+    assert_eq!(parser.peek_next().lexeme, "a");
+
+    parser.advance();
+    assert_eq!(parser.previous.lexeme, "var");
+    assert_eq!(parser.current.lexeme, "a");
+    assert_eq!(parser.peek_next().token_type, TokenType::Equal);
+}
+
+#[test]
+fn peek_next_skips_error_tokens_without_reporting_them() {
+    let mut parser: Parser = Parser::new(Scanner::new("a @ b c"));
+    parser.advance();
+
+    assert_eq!(parser.peek_next().lexeme, "b");
+    assert_eq!(parser.peek_next().lexeme, "b");
+    assert!(parser.diagnostics.is_empty());
+
+    // The error is reported once, when `advance` steps over it.
+    parser.advance();
+    assert_eq!(parser.current.lexeme, "b");
+    assert_eq!(messages(&parser), ["[line 1] Error: Unexpected character."]);
+
+    parser.panic_mode = false;
+    assert_eq!(parser.peek_next().lexeme, "c");
+    parser.advance();
+    parser.advance();
+    assert_eq!(parser.current.token_type, TokenType::Eof);
+    assert_eq!(messages(&parser), ["[line 1] Error: Unexpected character."]);
+}
+
+#[test]
+fn buffered_errors_respect_panic_mode() {
+    let mut parser: Parser = Parser::new(Scanner::new("a @ @ b"));
+    parser.advance();
+    parser.peek_next();
+
+    // Already panicking, so neither buffered error is reported.
+    parser.panic_mode = true;
+    parser.advance();
+
+    assert_eq!(parser.current.lexeme, "b");
+    assert!(parser.diagnostics.is_empty());
+}
@@ -0,0 +1,240 @@
+extern crate rvelox;
+
+mod common;
+
+use std::{
+    process::Output,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use common::{run_rvelox, TempScript};
+use rvelox::{
+    velox::{Velox, VeloxOptions},
+    vm::{InterpretResult, VM},
+};
+
+fn run_interactive(options: VeloxOptions, input: &str) -> String {
+    let mut output: Vec<u8> = Vec::new();
+    Velox::with_options(options).repl(input.as_bytes(), &mut output, true);
+
+    return String::from_utf8(output).unwrap();
+}
+
+#[test]
+fn custom_banner_and_prompts_are_shown() {
+    let options: VeloxOptions = VeloxOptions {
+        banner: Some("Welcome!".to_owned()),
+        prompt: "rv> ".to_owned(),
+        continuation_prompt: "rv| ".to_owned(),
+        ..VeloxOptions::default()
+    };
+
+    // The first statement continues onto a second line.
+    assert_eq!(run_interactive(options, "var a = 1 +\n2;\n"), "Welcome!\nrv> rv| rv> ");
+}
+
+#[test]
+fn quiet_drops_only_the_banner() {
+    let options: VeloxOptions = VeloxOptions { banner: None, ..VeloxOptions::default() };
+
+    assert_eq!(run_interactive(options, "var a = 1;\n"), "> > ");
+}
+
+#[test]
+fn embedders_get_no_banner_by_default() {
+    assert_eq!(run_interactive(VeloxOptions::default(), ""), "> ");
+}
+
+#[test]
+fn piped_input_shows_no_banner_or_prompt() {
+    let mut output: Vec<u8> = Vec::new();
+    Velox::with_options(VeloxOptions::default()).repl("var a = 1;\n".as_bytes(), &mut output, false);
+
+    assert!(output.is_empty());
+}
+
+#[test]
+fn interrupting_a_line_keeps_the_session() {
+    let mut vm: VM = VM::new();
+    assert_eq!(vm.interpret_repl("var kept = 1;"), InterpretResult::Ok);
+
+    let flag: Arc<AtomicBool> = vm.interrupt_flag();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        flag.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(vm.interpret_repl("do {} while (true);"), InterpretResult::RuntimeError);
+    interrupter.join().unwrap();
+
+    // The flag is spent on the interrupted line, so the next one runs to completion.
+    assert_eq!(vm.interpret_repl("kept = kept + 1;"), InterpretResult::Ok);
+    let globals: Vec<(&str, String)> =
+        vm.globals_iter().map(|(name, value)| (name, vm.stringify(&value))).collect();
+    assert_eq!(globals, vec![("kept", "2".to_owned())]);
+}
+
+#[test]
+fn piped_prompt_has_no_prompt_marker() {
+    let output: Output = run_rvelox(&[], &[], "print 1;\nprint 2;\n");
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n2\n");
+}
+
+#[test]
+fn reset_forgets_globals() {
+    let output: Output = run_rvelox(&[], &[], "var a = 1;\nprint a;\n:reset\nprint a;\n");
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Undefined variable 'a'.\n[<repl> line 1] in script\n",
+    );
+}
+
+#[test]
+fn load_runs_a_file_in_the_session() {
+    let script: TempScript =
+        TempScript::new("rvelox-load.vlx", "var greeting = \"hello\";\nprint \"loaded\";\n");
+
+    let output: Output = run_rvelox(&[], &[], &format!(
+        ":load {}\nprint greeting + \"!\";\n:load {}.missing\nprint \"alive\";\n",
+        script.path,
+        script.path,
+    ));
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "loaded\nhello!\nalive\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("Could not read file \"{}.missing\".\n", script.path),
+    );
+}
+
+#[test]
+fn load_reports_errors_and_keeps_the_repl_running() {
+    let script: TempScript =
+        TempScript::new("rvelox-load-error.vlx", "var defined = 1;\nprint -nil;\n");
+
+    let output: Output =
+        run_rvelox(&[], &[], &format!(":load {}\nprint defined;\n:load\n", script.path));
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "Operand must be a number, got nil.\n[{} line 2] in script\nUsage: :load <path>\n",
+            script.path,
+        ),
+    );
+}
+
+#[test]
+fn errors_name_the_file_they_come_from() {
+    let first: TempScript = TempScript::new("rvelox-first.vlx", "var count = 1;\nprint count;\n");
+    let second: TempScript =
+        TempScript::new("rvelox-second.vlx", "print count + 1;\n\nprint -nil;\n");
+
+    let output: Output = run_rvelox(&[], &[], &format!(
+        ":load {}\n:load {}\nprint -nil;\n",
+        first.path,
+        second.path,
+    ));
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n2\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "Operand must be a number, got nil.\n[{} line 3] in script\n\
+             Operand must be a number, got nil.\n[<repl> line 1] in script\n",
+            second.path,
+        ),
+    );
+}
+
+#[test]
+fn prompt_lines_need_no_semicolon() {
+    let output: Output = run_rvelox(&[], &[], "print 1 + 2\nvar a = 3\na\nprint a;\n");
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n3\n3\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn malformed_prompt_lines_still_error() {
+    let output: Output = run_rvelox(&[], &[], "print 1 + ;\nprint 1 2\nprint \"after\"\n");
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "after\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        concat!(
+            "[<repl> line 1] Error at ';': Expect expression.\n",
+            "[<repl> line 1] Error at '2': Expect ';' after value.\n",
+        ),
+    );
+}
+
+#[test]
+fn unfinished_prompt_lines_continue() {
+    let output: Output =
+        run_rvelox(&[], &[], "print 1 +\n2\n{\nprint 3;\n}\nprint 4 *\n\nprint 5\n");
+
+    // The blank line gives up on `print 4 *`, which is reported where the input ended.
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n3\n5\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[<repl> line 2] Error at end: Expect expression.\n",
+    );
+}
+
+#[test]
+fn restore_brings_back_saved_globals() {
+    let output: Output = run_rvelox(
+        &[],
+        &[],
+        "var a = 1;\n:save\na = 2;\nvar b = 3;\n:restore\nprint a;\nprint b;\n",
+    );
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Undefined variable 'b'.\n[<repl> line 1] in script\n",
+    );
+}
+
+#[test]
+fn restore_survives_a_fresh_allocator() {
+    // The reset replaces the allocator, so "saved" has to be interned again on restore.
+    let output: Output = run_rvelox(&[], &[], concat!(
+        "var s = \"saved\";\n:save\n:reset\n",
+        "var t = \"other\";\nvar u = \"more\";\n",
+        ":restore\nprint s + \"!\";\n",
+    ));
+
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "saved!\n");
+}
+
+#[test]
+fn runtime_error_leaves_the_vm_usable() {
+    let output: Output = run_rvelox(&[], &[], concat!(
+        "var a = 1;\n",
+        "var b = 2; var c = -\"three\"; var d = 4;\n",
+        "print a + b;\n",
+        "print c;\n",
+        "print a;\n",
+    ));
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        concat!(
+            "Operand must be a number, got string.\n[<repl> line 1] in script\n",
+            "Undefined variable 'c'.\n[<repl> line 1] in script\n",
+        ),
+    );
+}
@@ -0,0 +1,187 @@
+extern crate rvelox;
+
+use std::{
+    env,
+    process::{Command, Output},
+    sync::{Arc, Mutex},
+};
+
+use rvelox::{
+    chunk::{Chunk, Op},
+    debugger::{DebugContext, Debugger},
+    object::ObjAllocator,
+    value::Value,
+    vm::{InterpretResult, VM},
+};
+
+#[test]
+fn running_off_the_end_is_a_runtime_error() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.emit(Op::Nil, 1);
+    chunk.emit(Op::Pop, 1);
+
+    assert_eq!(VM::new().run_chunk(&chunk), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn truncated_operand_is_a_runtime_error() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.add_constant(Value::Nil);
+    chunk.write(Op::Constant.into(), 1);
+
+    assert_eq!(VM::new().run_chunk(&chunk), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn invalid_opcode_is_a_runtime_error() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.write(200, 1);
+    chunk.write(0, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(VM::new().run_chunk(&chunk), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn empty_chunk_is_a_runtime_error() {
+    assert_eq!(VM::new().run_chunk(&Chunk::new()), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn chunk_with_return_runs() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.emit(Op::Nil, 1);
+    chunk.emit(Op::Pop, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(VM::new().run_chunk(&chunk), InterpretResult::Ok);
+}
+
+// Records the numbers on the stack when the chunk returns.
+struct StackRecorder {
+    stack: Arc<Mutex<Vec<f64>>>,
+}
+
+impl Debugger for StackRecorder {
+    fn before_instruction(&mut self, context: &DebugContext) -> () {
+        if context.chunk.code[context.offset] != Op::Return.into() {
+            return;
+        }
+
+        let numbers = context.stack.iter().map(|value| match value {
+            Value::Number(number) => *number,
+            _ => f64::NAN,
+        });
+
+        *self.stack.lock().unwrap() = numbers.collect();
+    }
+}
+
+#[test]
+fn swap_exchanges_the_top_two_values() {
+    let mut chunk: Chunk = Chunk::new();
+    for number in 1..=3 {
+        let index: usize = chunk.add_constant(Value::Number(number as f64));
+        chunk.emit_with_byte(Op::Constant, index as u8, 1);
+    }
+    chunk.emit(Op::Swap, 1);
+    chunk.emit(Op::Return, 1);
+
+    let stack: Arc<Mutex<Vec<f64>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut vm: VM = VM::new();
+    vm.set_debugger(Box::new(StackRecorder { stack: stack.clone() }));
+
+    assert_eq!(vm.run_chunk(&chunk), InterpretResult::Ok);
+    assert_eq!(*stack.lock().unwrap(), vec![1.0, 3.0, 2.0]);
+}
+
+// Stack underflow is only caught in debug builds, and reported on stderr, so the chunk runs in a
+// child process: this same test, re-run with RVELOX_UNDERFLOW_CHILD set.
+#[cfg(debug_assertions)]
+#[test]
+fn stack_underflow_reports_the_instruction() {
+    if env::var("RVELOX_UNDERFLOW_CHILD").is_ok() {
+        let mut chunk: Chunk = Chunk::new();
+        let index: usize = chunk.add_constant(Value::Number(1.0));
+        chunk.emit_with_byte(Op::Constant, index as u8, 1);
+        chunk.emit(Op::Pop, 2);
+        chunk.emit(Op::Nil, 2);
+        chunk.emit(Op::Add, 3);
+        chunk.emit(Op::Return, 3);
+
+        assert_eq!(VM::new().run_chunk(&chunk), InterpretResult::RuntimeError);
+        return;
+    }
+
+    let output: Output = Command::new(env::current_exe().unwrap())
+        .args(["stack_underflow_reports_the_instruction", "--exact", "--nocapture"])
+        .env("RVELOX_UNDERFLOW_CHILD", "1")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Stack underflow.\n  at 0004    3 OP_ADD\n  stack: [ nil ]\n[line 3] in script\n",
+    );
+}
+
+// Like the test above: OP_SWAP with one value on the stack is an underflow, not a panic.
+#[cfg(debug_assertions)]
+#[test]
+fn swap_underflow_reports_the_instruction() {
+    if env::var("RVELOX_UNDERFLOW_CHILD").is_ok() {
+        let mut chunk: Chunk = Chunk::new();
+        let index: usize = chunk.add_constant(Value::Number(1.0));
+        chunk.emit_with_byte(Op::Constant, index as u8, 1);
+        chunk.emit(Op::Swap, 1);
+        chunk.emit(Op::Return, 1);
+
+        assert_eq!(VM::new().run_chunk(&chunk), InterpretResult::RuntimeError);
+        return;
+    }
+
+    let output: Output = Command::new(env::current_exe().unwrap())
+        .args(["swap_underflow_reports_the_instruction", "--exact", "--nocapture"])
+        .env("RVELOX_UNDERFLOW_CHILD", "1")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Stack underflow.\n  at 0002    | OP_SWAP\n  stack: [ 1 ]\n[line 1] in script\n",
+    );
+}
+
+#[test]
+fn locals_are_addressed_from_the_frame_base() {
+    let mut chunk: Chunk = Chunk::new();
+    for number in [10.0, 20.0, 30.0] {
+        chunk.add_constant(Value::Number(number));
+    }
+
+    // Slot zero is reserved, so the first value pushed is local 1.
+    chunk.emit_with_byte(Op::Constant, 0, 1);
+    chunk.emit_with_byte(Op::Constant, 1, 1);
+    chunk.emit_with_byte(Op::GetLocal, 1, 2);
+    chunk.emit_with_byte(Op::SetLocal, 2, 2);
+    chunk.emit(Op::Pop, 2);
+    chunk.emit_with_byte(Op::GetLocal, 2, 3);
+    chunk.emit_with_byte(Op::Constant, 2, 3);
+    chunk.emit_with_byte(Op::SetLocal, 1, 3);
+    chunk.emit(Op::Pop, 3);
+    chunk.emit(Op::Return, 3);
+
+    let stack: Arc<Mutex<Vec<f64>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut vm: VM = VM::new();
+    vm.set_debugger(Box::new(StackRecorder { stack: stack.clone() }));
+
+    assert_eq!(vm.run_chunk(&chunk), InterpretResult::Ok);
+    assert_eq!(*stack.lock().unwrap(), vec![30.0, 10.0, 10.0]);
+
+    let mut assembly: String = String::new();
+    chunk.write_chunk("locals", &ObjAllocator::new(), &mut assembly).unwrap();
+    assert!(assembly.contains("0004    2 OP_GET_LOCAL        1\n"), "{}", assembly);
+    assert!(assembly.contains("0006    | OP_SET_LOCAL        2\n"), "{}", assembly);
+}
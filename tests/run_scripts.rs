@@ -0,0 +1,259 @@
+extern crate rvelox;
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use rvelox::{
+    compiler::{Diagnostic, Severity},
+    velox::VeloxOptions,
+    vm::{InterpretResult, VM},
+};
+
+struct Expectations {
+    flags: Vec<String>,
+    output: Vec<String>,
+    diagnostics: Vec<String>,
+    runtime_error: Option<String>,
+    exit_code: Option<i32>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Expectations {
+        let mut expectations: Expectations = Expectations {
+            flags: Vec::new(),
+            output: Vec::new(),
+            diagnostics: Vec::new(),
+            runtime_error: None,
+            exit_code: None,
+        };
+
+        for line in source.lines() {
+            if let Some(index) = line.find("// flags: ") {
+                let flags: &str = &line[index + "// flags: ".len()..];
+                expectations.flags.extend(flags.split_whitespace().map(str::to_owned));
+            } else if let Some(index) = line.find("// expect: ") {
+                expectations.output.push(line[index + "// expect: ".len()..].to_owned());
+            } else if let Some(index) = line.find("// expect runtime error: ") {
+                let message: &str = &line[index + "// expect runtime error: ".len()..];
+                expectations.runtime_error = Some(message.to_owned());
+            } else if let Some(index) = line.find("// exit: ") {
+                expectations.exit_code = line[index + "// exit: ".len()..].trim().parse().ok();
+            } else if let Some(index) = line.find("// [line ") {
+                expectations.diagnostics.push(line[index + "// ".len()..].to_owned());
+            }
+        }
+
+        return expectations;
+    }
+
+    fn exit_code(&self) -> i32 {
+        if let Some(exit_code) = self.exit_code {
+            return exit_code;
+        }
+
+        let deny_warnings: bool = self.flags.iter().any(|flag| flag == "--deny-warnings");
+
+        if self.diagnostics.iter().any(|diagnostic| deny_warnings || diagnostic.contains("] Error")) {
+            return 65;
+        }
+
+        if self.runtime_error.is_some() {
+            return 70;
+        }
+
+        return 0;
+    }
+}
+
+fn collect_scripts(directory: &Path, scripts: &mut Vec<PathBuf>) -> () {
+    for entry in fs::read_dir(directory).expect("Could not read scripts directory.") {
+        let path: PathBuf = entry.unwrap().path();
+
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().map_or(false, |extension| extension == "vlx") {
+            scripts.push(path);
+        }
+    }
+}
+
+// A writer the VM can own while the test keeps a handle to read back what was written.
+#[derive(Clone)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    fn new() -> Capture {
+        return Capture(Arc::new(Mutex::new(Vec::new())));
+    }
+
+    fn contents(&self) -> String {
+        return String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned();
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        return Ok(bytes.len());
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        return Ok(());
+    }
+}
+
+#[derive(PartialEq)]
+struct ScriptOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: i32,
+}
+
+// Runs a script through the library the way the binary runs it with `flags`, capturing what it
+// prints. Errors name the script by its file name alone.
+fn run_script(path: &Path, flags: &[String]) -> ScriptOutput {
+    let source: String = fs::read_to_string(path).unwrap();
+    let name: &str = path.file_name().unwrap().to_str().unwrap();
+
+    let mut options: VeloxOptions = VeloxOptions::default();
+    for flag in flags {
+        assert_eq!(options.set_flag(flag), Ok(true), "{}: unknown flag {flag}", path.display());
+    }
+
+    let (mut stdout, mut stderr): (Capture, Capture) = (Capture::new(), Capture::new());
+    let mut vm: VM = VM::with_options(options);
+    vm.set_output(Box::new(stdout.clone()));
+    vm.set_error_output(Box::new(stderr.clone()));
+
+    // --check only compiles, and lists the diagnostics as JSON on stdout or as text on stderr.
+    let exit_code: i32 = if vm.options().check_only {
+        let diagnostics: Vec<Diagnostic> = vm.check_named(&source, name);
+        let options: &VeloxOptions = vm.options();
+
+        for diagnostic in diagnostics.iter() {
+            match options.json_diagnostics {
+                true => writeln!(stdout, "{}", diagnostic.to_json()).unwrap(),
+                false => writeln!(stderr, "{}", diagnostic.render()).unwrap(),
+            }
+        }
+
+        let failed: bool = diagnostics
+            .iter()
+            .any(|diagnostic| options.deny_warnings || diagnostic.severity == Severity::Error);
+
+        match failed {
+            true => 65,
+            false => 0,
+        }
+    } else {
+        match vm.interpret_named(&source, name) {
+            InterpretResult::Ok => 0,
+            InterpretResult::CompileError => 65,
+            InterpretResult::RuntimeError => 70,
+        }
+    };
+
+    return ScriptOutput { stdout: stdout.contents(), stderr: stderr.contents(), exit_code };
+}
+
+// Expected diagnostics are written as "[line N] ..." and qualified with the script's name here.
+fn check_script(path: &Path) -> Result<(), String> {
+    let source: String = fs::read_to_string(path).unwrap();
+    let mut expectations: Expectations = Expectations::parse(&source);
+    let name: &str = path.file_name().unwrap().to_str().unwrap();
+
+    for diagnostic in expectations.diagnostics.iter_mut() {
+        *diagnostic = diagnostic.replacen("[line ", &format!("[{name} line "), 1);
+    }
+
+    let output: ScriptOutput = run_script(path, &expectations.flags);
+
+    let stdout_lines: Vec<&str> = output.stdout.lines().collect();
+    if stdout_lines != expectations.output {
+        return Err(format!("expected output {:?}, got {:?}", expectations.output, stdout_lines));
+    }
+
+    let stderr_lines: Vec<&str> = output.stderr.lines().collect();
+    let diagnostic_count: usize = expectations.diagnostics.len().min(stderr_lines.len());
+
+    if stderr_lines[..diagnostic_count] != expectations.diagnostics[..] {
+        return Err(format!(
+            "expected diagnostics {:?}, got {:?}",
+            expectations.diagnostics,
+            stderr_lines,
+        ));
+    }
+
+    match &expectations.runtime_error {
+        Some(message) => {
+            if stderr_lines.get(diagnostic_count) != Some(&message.as_str()) {
+                return Err(format!("expected runtime error {:?}, got {:?}", message, output.stderr));
+            }
+        },
+        None => {
+            if stderr_lines.len() != diagnostic_count {
+                return Err(format!("unexpected stderr {:?}", output.stderr));
+            }
+        },
+    }
+
+    if output.exit_code != expectations.exit_code() {
+        return Err(format!(
+            "expected exit code {}, got {}",
+            expectations.exit_code(),
+            output.exit_code,
+        ));
+    }
+
+    return Ok(());
+}
+
+fn all_scripts() -> Vec<PathBuf> {
+    let mut scripts: Vec<PathBuf> = Vec::new();
+    collect_scripts(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts"), &mut scripts);
+    scripts.sort();
+
+    return scripts;
+}
+
+#[test]
+fn run_scripts() {
+    let scripts: Vec<PathBuf> = all_scripts();
+
+    let failures: Vec<String> = scripts
+        .iter()
+        .filter_map(|path| check_script(path).err().map(|error| format!("{}: {}", path.display(), error)))
+        .collect();
+
+    assert!(failures.is_empty(), "{} script(s) failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+// Superinstructions may only change the bytecode, so every script must behave the same with and
+// without them. Scripts whose flags report on the bytecode itself are left out.
+#[test]
+fn optimized_scripts_behave_the_same() {
+    let mut differences: Vec<String> = Vec::new();
+
+    for path in all_scripts() {
+        let mut flags: Vec<String> = Expectations::parse(&fs::read_to_string(&path).unwrap()).flags;
+        if flags.iter().any(|flag| flag == "--stats") {
+            continue;
+        }
+
+        flags.retain(|flag| flag != "--opt");
+        let plain: ScriptOutput = run_script(&path, &flags);
+
+        flags.push("--opt".to_owned());
+        let optimized: ScriptOutput = run_script(&path, &flags);
+
+        if plain != optimized {
+            differences.push(path.display().to_string());
+        }
+    }
+
+    assert!(differences.is_empty(), "optimized output differs for:\n{}", differences.join("\n"));
+}
@@ -0,0 +1,170 @@
+extern crate rvelox;
+
+use rvelox::scanner::{Scanner, Token, TokenType};
+
+fn scan(source: &str) -> Vec<(TokenType, &str)> {
+    let mut scanner: Scanner = Scanner::new(source);
+    let mut tokens: Vec<(TokenType, &str)> = Vec::new();
+
+    loop {
+        let token: Token = scanner.scan_token();
+        if token.token_type == TokenType::Eof {
+            return tokens;
+        }
+
+        tokens.push((token.token_type, token.lexeme));
+    }
+}
+
+#[test]
+fn range_between_integers() {
+    assert_eq!(
+        scan("1..3"),
+        [(TokenType::Number, "1"), (TokenType::DotDot, ".."), (TokenType::Number, "3")],
+    );
+    assert_eq!(
+        scan("1..=3"),
+        [(TokenType::Number, "1"), (TokenType::DotDotEqual, "..="), (TokenType::Number, "3")],
+    );
+}
+
+#[test]
+fn range_between_decimals() {
+    assert_eq!(
+        scan("1.5..2.5"),
+        [(TokenType::Number, "1.5"), (TokenType::DotDot, ".."), (TokenType::Number, "2.5")],
+    );
+}
+
+#[test]
+fn range_between_names() {
+    assert_eq!(
+        scan("a..b"),
+        [(TokenType::Identifier, "a"), (TokenType::DotDot, ".."), (TokenType::Identifier, "b")],
+    );
+}
+
+#[test]
+fn range_with_spaces() {
+    assert_eq!(
+        scan("1 .. 3"),
+        [(TokenType::Number, "1"), (TokenType::DotDot, ".."), (TokenType::Number, "3")],
+    );
+}
+
+#[test]
+fn three_dots_are_a_range_then_a_dot() {
+    assert_eq!(
+        scan("1...3"),
+        [
+            (TokenType::Number, "1"),
+            (TokenType::DotDot, ".."),
+            (TokenType::Dot, "."),
+            (TokenType::Number, "3"),
+        ],
+    );
+}
+
+#[test]
+fn single_dot_is_still_property_access() {
+    assert_eq!(
+        scan("1.floor"),
+        [(TokenType::Number, "1"), (TokenType::Dot, "."), (TokenType::Identifier, "floor")],
+    );
+}
+
+#[test]
+fn trailing_decimal_point_is_still_an_error() {
+    assert_eq!(scan("5.")[0].0, TokenType::Error);
+}
+
+#[test]
+fn in_is_a_keyword() {
+    assert_eq!(
+        scan("in if inner i"),
+        [
+            (TokenType::In, "in"),
+            (TokenType::If, "if"),
+            (TokenType::Identifier, "inner"),
+            (TokenType::Identifier, "i"),
+        ],
+    );
+}
+
+#[test]
+fn every_keyword_is_scanned_as_its_token() {
+    let keywords: [(&str, TokenType); 18] = [
+        ("and", TokenType::And),
+        ("class", TokenType::Class),
+        ("do", TokenType::Do),
+        ("else", TokenType::Else),
+        ("false", TokenType::False),
+        ("for", TokenType::For),
+        ("fun", TokenType::Fun),
+        ("if", TokenType::If),
+        ("in", TokenType::In),
+        ("nil", TokenType::Nil),
+        ("or", TokenType::Or),
+        ("print", TokenType::Print),
+        ("return", TokenType::Return),
+        ("super", TokenType::Super),
+        ("this", TokenType::This),
+        ("true", TokenType::True),
+        ("var", TokenType::Var),
+        ("while", TokenType::While),
+    ];
+
+    for (keyword, token_type) in keywords {
+        assert_eq!(scan(keyword), [(token_type, keyword)], "{keyword}");
+
+        // Neither a prefix nor an extension of a keyword is one.
+        let longer: String = format!("{keyword}s");
+        assert_eq!(scan(&longer), [(TokenType::Identifier, longer.as_str())]);
+        let shorter: &str = &keyword[..keyword.len() - 1];
+        assert_eq!(scan(shorter), [(TokenType::Identifier, shorter)]);
+    }
+}
+
+#[test]
+fn print_is_an_identifier_without_the_keyword() {
+    let mut scanner: Scanner = Scanner::new("print");
+    scanner.print_keyword = false;
+
+    assert_eq!(scanner.scan_token().token_type, TokenType::Identifier);
+}
+
+#[test]
+fn current_line_follows_skipped_newlines() {
+    let mut scanner: Scanner = Scanner::new("a\n\n  b // comment\n\n");
+    assert_eq!(scanner.current_line(), 1);
+
+    assert_eq!(scanner.scan_token().line, 1);
+    assert_eq!(scanner.current_line(), 1);
+
+    // Scanning `b` skips the blank lines before it.
+    assert_eq!(scanner.scan_token().line, 3);
+    assert_eq!(scanner.current_line(), 3);
+    assert!(!scanner.at_end());
+
+    assert_eq!(scanner.scan_token().token_type, TokenType::Eof);
+    assert_eq!(scanner.current_line(), 5);
+    assert!(scanner.at_end());
+}
+
+#[test]
+fn reset_scans_a_new_source_from_line_one() {
+    let mut scanner: Scanner = Scanner::new("a\n\nb");
+    scanner.print_keyword = false;
+
+    assert_eq!(scanner.scan_token().line, 1);
+    assert_eq!(scanner.scan_token().line, 3);
+    assert_eq!(scanner.scan_token().token_type, TokenType::Eof);
+
+    scanner.reset("\n  print");
+    let token: Token = scanner.scan_token();
+
+    assert_eq!((token.token_type, token.lexeme, token.line), (TokenType::Identifier, "print", 2));
+    assert_eq!(token.column, 3);
+    assert_eq!(scanner.scan_token().token_type, TokenType::Eof);
+    assert!(scanner.at_end());
+}
@@ -0,0 +1,60 @@
+extern crate rvelox;
+
+use rvelox::{
+    scanner::{Scanner, Token, TokenType},
+    vm::{InterpretResult, VM},
+};
+
+fn lines(source: &str) -> Vec<(TokenType, usize)> {
+    let mut scanner: Scanner = Scanner::new(source);
+    let mut tokens: Vec<(TokenType, usize)> = Vec::new();
+
+    loop {
+        let token: Token = scanner.scan_token();
+        tokens.push((token.token_type, token.line));
+
+        if token.token_type == TokenType::Eof {
+            return tokens;
+        }
+    }
+}
+
+#[test]
+fn byte_order_mark_is_skipped() {
+    let source: &str = "var greeting = \"hi\";\nprint greeting + \"!\";\n";
+    let with_bom: String = format!("\u{FEFF}{source}");
+
+    assert_eq!(
+        rvelox::compile_to_assembly(&with_bom).unwrap(),
+        rvelox::compile_to_assembly(source).unwrap(),
+    );
+    assert_eq!(lines(&with_bom), lines(source));
+}
+
+#[test]
+fn every_line_ending_counts_lines() {
+    let expected: Vec<(TokenType, usize)> = vec![
+        (TokenType::Identifier, 1),
+        (TokenType::Identifier, 2),
+        (TokenType::Identifier, 3),
+        (TokenType::Eof, 3),
+    ];
+
+    assert_eq!(lines("a\nb\nc"), expected);
+    assert_eq!(lines("a\r\nb\r\nc"), expected);
+    assert_eq!(lines("a\rb\rc"), expected);
+    assert_eq!(lines("a // comment\rb\r\"\"\"\r\"\"\""), [
+        (TokenType::Identifier, 1),
+        (TokenType::Identifier, 2),
+        (TokenType::String, 4),
+        (TokenType::Eof, 4),
+    ]);
+}
+
+#[test]
+fn empty_and_blank_sources_run() {
+    for source in ["", "\u{FEFF}", " \t\r\n\r", "// only a comment"] {
+        assert_eq!(lines(source).len(), 1, "{source:?}");
+        assert_eq!(VM::new().interpret(source), InterpretResult::Ok, "{:?}", source);
+    }
+}
@@ -0,0 +1,128 @@
+extern crate rvelox;
+
+mod common;
+
+use std::collections::HashMap;
+
+use rvelox::{
+    object::{ObjAllocator, ObjRef},
+    table::{self, Table},
+};
+
+use common::Random;
+
+#[test]
+fn table_agrees_with_a_hash_map() {
+    let mut random: Random = Random(0x9E37_79B9_7F4A_7C15);
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+
+    // Enough keys to force several resizes, few enough that deletes and re-inserts collide.
+    let keys: Vec<ObjRef<String>> =
+        (0..200).map(|index| allocator.intern(format!("key{index}"))).collect();
+
+    let mut table: Table<u64> = Table::new();
+    let mut model: HashMap<ObjRef<String>, u64> = HashMap::new();
+
+    for step in 0..50_000 {
+        let key: ObjRef<String> = keys[random.below(keys.len() as u64) as usize];
+        let hash: u32 = allocator.hash(key);
+
+        match random.below(3) {
+            0 => {
+                let value: u64 = random.next();
+                assert_eq!(table.set(key, hash, value), model.insert(key, value).is_none());
+            },
+            1 => assert_eq!(table.delete(key, hash), model.remove(&key).is_some()),
+            _ => assert_eq!(table.get(key, hash), model.get(&key)),
+        }
+
+        assert_eq!(table.len(), model.len(), "after step {}", step);
+    }
+
+    let mut entries: Vec<(ObjRef<String>, u64)> =
+        table.iter().map(|(key, &value)| (key, value)).collect();
+    let mut expected: Vec<(ObjRef<String>, u64)> = model.into_iter().collect();
+    entries.sort_by_key(|&(key, _)| allocator.deref(key).clone());
+    expected.sort_by_key(|&(key, _)| allocator.deref(key).clone());
+
+    assert_eq!(entries, expected);
+}
+
+#[test]
+fn deleted_keys_leave_later_keys_reachable() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let keys: Vec<ObjRef<String>> =
+        (0..3).map(|index| allocator.intern(format!("key{index}"))).collect();
+
+    // With one hash for every key, they all land in a single probe sequence.
+    let mut table: Table<usize> = Table::new();
+    for (index, &key) in keys.iter().enumerate() {
+        table.set(key, 7, index);
+    }
+
+    assert!(table.delete(keys[0], 7));
+    assert_eq!(table.get(keys[2], 7), Some(&2));
+
+    assert!(table.set(keys[0], 7, 10));
+    assert_eq!(table.get(keys[0], 7), Some(&10));
+    assert_eq!(table.len(), 3);
+}
+
+#[test]
+fn add_all_copies_every_entry() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let (a, b) = (allocator.intern("a".to_owned()), allocator.intern("b".to_owned()));
+
+    let mut from: Table<i32> = Table::new();
+    from.set(a, allocator.hash(a), 1);
+    from.set(b, allocator.hash(b), 2);
+
+    let mut to: Table<i32> = Table::with_capacity(1);
+    to.set(a, allocator.hash(a), 0);
+    from.add_all(&mut to);
+
+    assert_eq!(to.len(), 2);
+    assert_eq!(to.get(a, allocator.hash(a)), Some(&1));
+    assert_eq!(to.get(b, allocator.hash(b)), Some(&2));
+}
+
+#[test]
+fn strings_are_found_by_contents() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let reference: ObjRef<String> = allocator.intern("hello".to_owned());
+
+    let mut table: Table<()> = Table::new();
+    table.set(reference, table::hash_string("hello"), ());
+
+    let found = |value: &str| -> Option<ObjRef<String>> {
+        return table.find_string(table::hash_string(value), |key| allocator.deref(key) == value);
+    };
+
+    assert_eq!(found("hello"), Some(reference));
+    assert_eq!(found("world"), None);
+    assert_eq!(allocator.hash(reference), table::hash_string("hello"));
+    assert_eq!(allocator.find_interned("hello"), Some(reference));
+    assert!(allocator.is_interned(reference));
+}
+
+// "glbvs" and "yacxa" have the same FNV-1a hash.
+#[test]
+fn colliding_strings_stay_distinct() {
+    assert_eq!(table::hash_string("glbvs"), table::hash_string("yacxa"));
+
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let first: ObjRef<String> = allocator.intern("glbvs".to_owned());
+    let second: ObjRef<String> = allocator.intern("yacxa".to_owned());
+
+    assert_ne!(first, second);
+    assert_eq!(allocator.find_interned("glbvs"), Some(first));
+    assert_eq!(allocator.find_interned("yacxa"), Some(second));
+    assert_eq!(allocator.intern("yacxa".to_owned()), second);
+
+    let mut table: Table<i32> = Table::new();
+    table.set(first, allocator.hash(first), 1);
+    table.set(second, allocator.hash(second), 2);
+
+    assert_eq!(table.get(first, allocator.hash(first)), Some(&1));
+    assert_eq!(table.get(second, allocator.hash(second)), Some(&2));
+}
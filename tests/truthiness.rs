@@ -0,0 +1,102 @@
+extern crate rvelox;
+
+use rvelox::{
+    value::Value,
+    velox::VeloxOptions,
+    vm::{InterpretResult, VM},
+};
+
+// Every kind of value, as source, with whether it is truthy. Only nil and false are falsy.
+const VALUES: &[(&str, bool)] = &[
+    ("nil", false),
+    ("false", false),
+    ("true", true),
+    ("0", true),
+    ("-0", true),
+    ("0 / 0", true),
+    ("\"\"", true),
+    ("\"false\"", true),
+    ("0..0", true),
+    ("1..=3", true),
+    ("buffer()", true),
+    ("clock", true),
+];
+
+fn result(vm: &VM) -> Value {
+    return vm.globals_iter().find(|(name, _)| *name == "result").unwrap().1;
+}
+
+fn assert_bool(value: Value, expected: bool, source: &str) -> () {
+    match value {
+        Value::Bool(value) => assert_eq!(value, expected, "{}", source),
+        _ => panic!("{} did not produce a bool", source),
+    }
+}
+
+#[test]
+fn not_negates_truthiness() {
+    for &(value, truthy) in VALUES {
+        let mut vm: VM = VM::new();
+        let source: String = format!("var result = !({value});");
+
+        assert_eq!(vm.interpret(&source), InterpretResult::Ok, "{}", source);
+        assert_bool(result(&vm), !truthy, &source);
+    }
+}
+
+#[test]
+fn loop_conditions_follow_truthiness() {
+    for &(value, truthy) in VALUES {
+        let mut vm: VM = VM::new();
+        // The first check sees the value and the second sees false, so a truthy value runs the
+        // body twice.
+        let source: String = format!(
+            "var runs = 0; var condition = nil; var next = {value};\n\
+             do {{ runs = runs + 1; condition = next; next = false; }} while (condition);\n\
+             var result = runs == 2;"
+        );
+
+        assert_eq!(vm.interpret(&source), InterpretResult::Ok, "{}", source);
+        assert_bool(result(&vm), truthy, &source);
+    }
+}
+
+#[test]
+fn strict_conditions_accept_only_bools() {
+    for &(value, _) in VALUES {
+        let options: VeloxOptions = VeloxOptions { strict_conditions: true, ..Default::default() };
+        let mut vm: VM = VM::with_options(options);
+        let source: String = format!(
+            "var condition = nil; var next = {value};\n\
+             do {{ condition = next; next = false; }} while (condition);"
+        );
+
+        let expected: InterpretResult = match value {
+            "true" | "false" => InterpretResult::Ok,
+            _ => InterpretResult::RuntimeError,
+        };
+
+        assert_eq!(vm.interpret(&source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn type_names_cover_every_kind() {
+    let names: Vec<&str> = VALUES
+        .iter()
+        .map(|(value, _)| {
+            let mut vm: VM = VM::new();
+            vm.interpret(&format!("var result = {value};"));
+
+            result(&vm).type_name()
+        })
+        .collect();
+
+    assert_eq!(
+        names,
+        [
+            "nil", "bool", "bool", "number", "number", "number", "string", "string", "range",
+            "range", "buffer", "native",
+        ],
+    );
+}
@@ -0,0 +1,113 @@
+extern crate rvelox;
+
+mod common;
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use rvelox::{
+    natives,
+    object::{Buffer, ObjAllocator, Range},
+    value::Value,
+    value_key::ValueKey,
+};
+
+use common::Random;
+
+fn random_value(random: &mut Random, allocator: &mut ObjAllocator) -> Value {
+    match random.below(8) {
+        0 => return Value::Nil,
+        1 => return Value::Bool(random.below(2) == 0),
+        // Few distinct numbers and strings, so that equal pairs come up often.
+        2 => {
+            let numbers: [f64; 7] = [0.0, -0.0, 1.0, -1.0, 0.5, f64::NAN, f64::INFINITY];
+            return Value::Number(numbers[random.below(7) as usize]);
+        },
+        3 => return Value::Number(f64::from_bits(random.next())),
+        4 => return Value::Number(random.below(4) as f64),
+        5 => {
+            let string: String = ["", "a", "b", "ab"][random.below(4) as usize].to_owned();
+            return Value::String(allocator.intern(string));
+        },
+        6 => {
+            let range: Range = Range { start: 0.0, end: random.below(2) as f64, inclusive: false };
+            return Value::Range(allocator.alloc(range));
+        },
+        _ => return Value::Native(natives::find_native("print").unwrap()),
+    }
+}
+
+fn hash(key: &ValueKey) -> u64 {
+    let mut hasher: DefaultHasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    return hasher.finish();
+}
+
+fn is_nan(value: Value) -> bool {
+    return matches!(value, Value::Number(number) if number.is_nan());
+}
+
+#[test]
+fn equal_keys_hash_equally() {
+    let mut random: Random = Random(0x2545_F491_4F6C_DD1D);
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+
+    for _ in 0..10_000 {
+        let a: Value = random_value(&mut random, &mut allocator);
+        let b: Value = random_value(&mut random, &mut allocator);
+        let (key_a, key_b) = (ValueKey::new(a).unwrap(), ValueKey::new(b).unwrap());
+
+        assert!(key_a == key_a, "keys must equal themselves");
+
+        if key_a == key_b {
+            assert_eq!(hash(&key_a), hash(&key_b));
+        }
+
+        // Keys agree with `==` everywhere but NaN.
+        if !is_nan(a) || !is_nan(b) {
+            assert_eq!(key_a == key_b, a.equals(&b));
+        }
+    }
+}
+
+#[test]
+fn zeroes_and_nans_are_single_keys() {
+    let mut map: HashMap<ValueKey, &str> = HashMap::new();
+
+    map.insert(ValueKey::new(Value::Number(0.0)).unwrap(), "zero");
+    map.insert(ValueKey::new(Value::Number(-0.0)).unwrap(), "negative zero");
+    map.insert(ValueKey::new(Value::Number(f64::NAN)).unwrap(), "nan");
+    map.insert(ValueKey::new(Value::Number(-f64::NAN)).unwrap(), "negative nan");
+
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&ValueKey::new(Value::Number(0.0)).unwrap()], "negative zero");
+    assert_eq!(map[&ValueKey::new(Value::Number(f64::NAN)).unwrap()], "negative nan");
+}
+
+#[test]
+fn strings_with_equal_contents_are_one_key() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let mut map: HashMap<ValueKey, i32> = HashMap::new();
+
+    let first: Value = Value::String(allocator.intern("key".to_owned()));
+    let second: Value = Value::String(allocator.intern(String::from("ke") + "y"));
+
+    map.insert(ValueKey::new(first).unwrap(), 1);
+    map.insert(ValueKey::new(second).unwrap(), 2);
+
+    assert_eq!(map.len(), 1);
+    assert_eq!(map[&ValueKey::new(first).unwrap()], 2);
+}
+
+#[test]
+fn buffers_are_rejected() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let buffer: Value = Value::Buffer(allocator.alloc(Buffer { contents: String::new() }));
+
+    assert_eq!(
+        ValueKey::new(buffer).err(),
+        Some("Value of type buffer can't be used as a key.".to_owned()),
+    );
+}
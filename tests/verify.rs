@@ -0,0 +1,103 @@
+extern crate rvelox;
+
+use rvelox::{
+    chunk::{Chunk, Op, VerifyError},
+    object::ObjAllocator,
+    value::Value,
+};
+
+#[test]
+fn valid_chunk() {
+    let mut allocator: ObjAllocator = ObjAllocator::new();
+    let mut chunk: Chunk = Chunk::new();
+
+    let name: usize = chunk.add_constant(Value::String(allocator.intern("x".to_owned())));
+    let number: usize = chunk.add_constant(Value::Number(1.0));
+    chunk.emit_with_byte(Op::Constant, number as u8, 1);
+    chunk.emit_with_byte(Op::DefineGlobal, name as u8, 1);
+
+    let start: usize = chunk.code.len();
+    chunk.emit(Op::True, 2);
+    let jump = chunk.emit_jump(Op::JumpIfFalse, 2);
+    chunk.emit(Op::Pop, 2);
+    chunk.emit_loop(start, 2).unwrap();
+    chunk.patch_jump(jump).unwrap();
+    chunk.emit(Op::Pop, 2);
+    chunk.emit(Op::Return, 3);
+
+    assert_eq!(chunk.verify(), Ok(()));
+}
+
+#[test]
+fn invalid_opcode() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.write(u8::MAX, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::InvalidOpcode { offset: 0, byte: u8::MAX }));
+}
+
+#[test]
+fn truncated_instruction() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.emit(Op::Return, 1);
+    chunk.write(Op::Constant.into(), 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::TruncatedInstruction { offset: 1 }));
+}
+
+#[test]
+fn constant_out_of_range() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.add_constant(Value::Nil);
+    chunk.emit_with_byte(Op::Constant, 1, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::ConstantOutOfRange { offset: 0, index: 1 }));
+}
+
+#[test]
+fn global_name_is_not_a_string() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.add_constant(Value::Number(1.0));
+    chunk.emit_with_byte(Op::GetGlobal, 0, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::ExpectedStringConstant { offset: 0, index: 0 }));
+}
+
+#[test]
+fn jump_off_instruction_boundary() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.add_constant(Value::Nil);
+    chunk.emit_with_short(Op::Jump, 1, 1);
+    chunk.emit_with_byte(Op::Constant, 0, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::JumpIntoInstruction { offset: 0, target: 4 }));
+}
+
+#[test]
+fn jump_out_of_bounds() {
+    let mut chunk: Chunk = Chunk::new();
+    chunk.emit_with_short(Op::Jump, 100, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::JumpOutOfBounds { offset: 0 }));
+
+    let mut chunk: Chunk = Chunk::new();
+    chunk.emit_with_short(Op::Loop, 4, 1);
+    chunk.emit(Op::Return, 1);
+
+    assert_eq!(chunk.verify(), Err(VerifyError::JumpOutOfBounds { offset: 0 }));
+}
+
+#[test]
+fn missing_return() {
+    let mut chunk: Chunk = Chunk::new();
+    assert_eq!(chunk.verify(), Err(VerifyError::MissingReturn));
+
+    chunk.emit(Op::Nil, 1);
+    chunk.emit(Op::Pop, 1);
+    assert_eq!(chunk.verify(), Err(VerifyError::MissingReturn));
+}